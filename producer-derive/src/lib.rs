@@ -0,0 +1,195 @@
+//! `producer` 的派生宏，为结构体生成 `ToEvent` 与 `FromEvent` 实现
+//!
+//! 字段属性：
+//! - `#[event(rename = "...")]`：使用指定的事件字段名
+//! - `#[event(skip)]`：忽略该字段，`FromEvent` 时使用 `Default::default()`
+//! - `#[event(flatten)]`：将嵌套结构体的字段展开到当前事件中
+//!
+//! `FromEvent` 时缺少或为 `Nil` 的字段只有 `Option<T>` 视为 `None`，其他类型返回 `Error::invalid_data`。
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Fields, LitStr};
+
+/// 字段上的 `#[event(...)]` 属性
+#[derive(Default)]
+struct FieldAttr {
+    rename: Option<String>,
+    skip: bool,
+    flatten: bool,
+}
+
+impl FieldAttr {
+    fn parse(field: &syn::Field) -> syn::Result<Self> {
+        let mut attr = FieldAttr::default();
+        for meta in field.attrs.iter().filter(|a| a.path().is_ident("event")) {
+            meta.parse_nested_meta(|nested| {
+                if nested.path.is_ident("rename") {
+                    let name: LitStr = nested.value()?.parse()?;
+                    attr.rename = Some(name.value());
+                } else if nested.path.is_ident("skip") {
+                    attr.skip = true;
+                } else if nested.path.is_ident("flatten") {
+                    attr.flatten = true;
+                } else {
+                    return Err(nested.error("expected `rename`, `skip` or `flatten`"));
+                }
+                Ok(())
+            })?;
+        }
+        if attr.flatten && attr.rename.is_some() {
+            return Err(syn::Error::new_spanned(
+                field,
+                "`flatten` can't be used together with `rename`",
+            ));
+        }
+        Ok(attr)
+    }
+}
+
+fn named_fields(input: &DeriveInput) -> syn::Result<Vec<(syn::Ident, syn::Type, FieldAttr)>> {
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    input,
+                    "event derive only supports structs with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(syn::Error::new_spanned(
+                input,
+                "event derive only supports structs",
+            ))
+        }
+    };
+    fields
+        .iter()
+        .map(|field| {
+            Ok((
+                field.ident.clone().unwrap(),
+                field.ty.clone(),
+                FieldAttr::parse(field)?,
+            ))
+        })
+        .collect()
+}
+
+fn event_name(ident: &syn::Ident, attr: &FieldAttr) -> String {
+    attr.rename.clone().unwrap_or_else(|| ident.to_string())
+}
+
+/// 按类型名判断是否为 `Option<T>`，缺少的字段只对 `Option` 视为 `None`
+fn is_option(ty: &syn::Type) -> bool {
+    match ty {
+        syn::Type::Path(path) if path.qself.is_none() => path
+            .path
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == "Option"),
+        _ => false,
+    }
+}
+
+/// 通过 `From<T> for Value` 将结构体字段转为事件字段
+#[proc_macro_derive(ToEvent, attributes(event))]
+pub fn derive_to_event(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_to_event(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+fn expand_to_event(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let inserts = named_fields(input)?
+        .into_iter()
+        .filter(|(_, _, attr)| !attr.skip)
+        .map(|(ident, _, attr)| {
+            if attr.flatten {
+                quote! {
+                    event.extend(::producer::ToEvent::to(&self.#ident));
+                }
+            } else {
+                let name = event_name(&ident, &attr);
+                quote! {
+                    event.insert(
+                        #name.to_owned(),
+                        ::producer::Value::from(::std::clone::Clone::clone(&self.#ident)),
+                    );
+                }
+            }
+        });
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::producer::ToEvent for #name #ty_generics #where_clause {
+            fn to(&self) -> ::producer::Event {
+                let mut event = ::producer::Event::new();
+                #(#inserts)*
+                event
+            }
+        }
+    })
+}
+
+/// 通过 `TryFrom<Value> for T` 从事件字段构造结构体
+#[proc_macro_derive(FromEvent, attributes(event))]
+pub fn derive_from_event(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_from_event(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+fn expand_from_event(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let fields = named_fields(input)?.into_iter().map(|(ident, ty, attr)| {
+        if attr.skip {
+            quote! { #ident: ::std::default::Default::default() }
+        } else if attr.flatten {
+            quote! { #ident: ::producer::FromEvent::from_event(event)? }
+        } else {
+            let name = event_name(&ident, &attr);
+            // `Option` 字段缺少或为 `Nil` 时为 `None`，其他字段缺少或为 `Nil` 时报错
+            let value = if is_option(&ty) {
+                quote! { event.get(#name).cloned().unwrap_or(::producer::Value::Nil) }
+            } else {
+                quote! {
+                    match event.get(#name) {
+                        None | Some(::producer::Value::Nil) => {
+                            return Err(::producer::Error::invalid_data(&format!(
+                                "missing field {}",
+                                #name
+                            )))
+                        }
+                        Some(value) => value.clone(),
+                    }
+                }
+            };
+            quote! {
+                #ident: ::std::convert::TryFrom::try_from(#value)
+                    .map_err(|err: ::producer::Error| {
+                        ::producer::Error::invalid_type(&format!(
+                            "failed to parse event field {} - {}",
+                            #name,
+                            err.get_msg()
+                        ))
+                    })?
+            }
+        }
+    });
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::producer::FromEvent for #name #ty_generics #where_clause {
+            fn from_event(event: &::producer::Event) -> ::producer::Result<Self> {
+                Ok(Self {
+                    #(#fields,)*
+                })
+            }
+        }
+    })
+}
@@ -0,0 +1,257 @@
+use std::convert::TryFrom;
+use std::fmt::Display;
+
+use super::{key, Config};
+use crate::{DataType, Error, Result, ToType, Value};
+
+/// 读取原始值，用于在转换前检查类型
+struct Raw(Value);
+
+impl TryFrom<Value> for Raw {
+    type Error = Error;
+    fn try_from(value: Value) -> Result<Self> {
+        Ok(Raw(value))
+    }
+}
+
+/// 启动时收集到的所有配置问题
+#[derive(Debug, Default)]
+pub struct ConfigErrors {
+    problems: Vec<String>,
+}
+
+impl ConfigErrors {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push<K: Display, M: Display>(&mut self, key: K, msg: M) {
+        self.problems.push(format!("{}: {}", key, msg));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.problems.is_empty()
+    }
+
+    pub fn problems(&self) -> &[String] {
+        &self.problems
+    }
+
+    /// 没有问题时返回 `value`，否则返回列出所有问题的 `Error::invalid_param`
+    pub fn into_result<T>(self, value: Option<T>) -> Result<T> {
+        match value {
+            Some(value) if self.is_empty() => Ok(value),
+            _ => Err(Error::invalid_param(&format!(
+                "found {} problem(s) in config:\n  {}",
+                self.problems.len(),
+                self.problems.join("\n  ")
+            ))),
+        }
+    }
+}
+
+/// 配置中的一个表，读取时记录类型错误、缺失的键，结束时记录未知的键
+pub struct Section<'a, C: Config> {
+    config: &'a C,
+    key: String,
+    known: Vec<String>,
+    errors: &'a mut ConfigErrors,
+}
+
+impl<'a, C: Config> Section<'a, C> {
+    pub fn new(config: &'a C, key: &str, errors: &'a mut ConfigErrors) -> Self {
+        Self {
+            config,
+            key: key.to_owned(),
+            known: vec![],
+            errors,
+        }
+    }
+
+    /// 表下某一项的完整键名
+    pub fn key(&self, name: &str) -> String {
+        if name.is_empty() {
+            self.key.clone()
+        } else {
+            key::join(&self.key, name)
+        }
+    }
+
+    pub fn exists(&self) -> bool {
+        self.config.get_keys(self.key.as_str()).is_ok()
+    }
+
+    /// 表下所有的键名，读取这些键名时无需再通过其他方法标记
+    pub fn keys(&mut self) -> Vec<String> {
+        let keys = self.config.get_keys(self.key.as_str()).unwrap_or_default();
+        self.known.extend(keys.iter().cloned());
+        keys
+    }
+
+    pub fn error<M: Display>(&mut self, name: &str, msg: M) {
+        let key = self.key(name);
+        self.errors.push(key, msg);
+    }
+
+    /// 记录结果中的错误
+    pub fn check<T>(&mut self, name: &str, result: Result<T>) -> Option<T> {
+        result.map_err(|err| self.error(name, err.get_msg())).ok()
+    }
+
+    /// 读取必须存在的键
+    pub fn required<T: TryFrom<Value, Error = Error> + ToType>(&mut self, name: &str) -> Option<T> {
+        let value = self.read(name);
+        if value.is_none() && !self.has(name) {
+            self.error(name, "missing required key");
+        }
+        value
+    }
+
+    /// 读取可选的键，不存在时返回 `None`
+    pub fn optional<T: TryFrom<Value, Error = Error> + ToType>(&mut self, name: &str) -> Option<T> {
+        self.read(name)
+    }
+
+    /// 读取可选的键，不存在或类型错误时返回默认值
+    pub fn get_or<T: TryFrom<Value, Error = Error> + ToType>(
+        &mut self,
+        name: &str,
+        default: T,
+    ) -> T {
+        self.read(name).unwrap_or(default)
+    }
+
+    /// 读取可选的键，不检查类型
+    pub fn value(&mut self, name: &str) -> Option<Value> {
+        self.known.push(name.to_owned());
+        match self.config.get_value::<_, Raw>(self.key(name)) {
+            Ok(Raw(value)) => Some(value),
+            Err(_) => None,
+        }
+    }
+
+    /// 进入子表，子表中的问题记录在同一个 `ConfigErrors` 中
+    pub fn section(&mut self, name: &str) -> Section<'_, C> {
+        self.known.push(name.to_owned());
+        let key = self.key(name);
+        Section::new(self.config, &key, self.errors)
+    }
+
+    /// 记录表中没有被读取过的键
+    pub fn finish(self) {
+        let keys = match self.config.get_keys(self.key.as_str()) {
+            Ok(keys) => keys,
+            Err(_) => return,
+        };
+        for name in keys {
+            if !self.known.contains(&name) {
+                self.errors.push(key::join(&self.key, &name), "unknown key");
+            }
+        }
+    }
+
+    fn has(&self, name: &str) -> bool {
+        self.config.get_value::<_, Raw>(self.key(name)).is_ok()
+    }
+
+    fn read<T: TryFrom<Value, Error = Error> + ToType>(&mut self, name: &str) -> Option<T> {
+        self.known.push(name.to_owned());
+        let value = match self.config.get_value::<_, Raw>(self.key(name)) {
+            Ok(Raw(value)) => value,
+            Err(err) if err.is_invalid_index_err() => return None,
+            Err(err) => {
+                self.error(name, err.get_msg());
+                return None;
+            }
+        };
+
+        let expect = T::get_type();
+        let actual = value.get_type();
+        let compatible =
+            expect == actual || (expect == DataType::Number && actual == DataType::Integer);
+        if !compatible {
+            // 环境变量与 `--set` 的覆盖值是字符串，由配置按读取的类型转换
+            if actual == DataType::String {
+                if let Ok(value) = self.config.get_value::<_, T>(self.key(name)) {
+                    return Some(value);
+                }
+            }
+            self.error(name, format!("expects {} but got {}", expect, actual));
+            return None;
+        }
+        let value = if expect == DataType::Number {
+            value.cast(DataType::Number)
+        } else {
+            Ok(value)
+        };
+        let value = value.and_then(T::try_from);
+        self.check(name, value)
+    }
+}
+
+#[cfg(test)]
+fn check(toml: &str) -> ConfigErrors {
+    let config = super::TomlConfig::from_string(toml).unwrap();
+    let mut errors = ConfigErrors::new();
+    let mut section = Section::new(&config, "input", &mut errors);
+    section.required::<String>("rpc_uri");
+    section.get_or::<u32>("max_thread", 1);
+    section.optional::<f64>("ratio");
+    section.optional::<Vec<String>>("contracts");
+    {
+        let mut filter = section.section("filter");
+        filter.required::<i64>("from_block");
+        filter.finish();
+    }
+    section.finish();
+    errors
+}
+
+#[test]
+fn test_section() {
+    let errors = check(
+        r#"
+        [input]
+        rpc_uri = "http://localhost:8545"
+        ratio = 1
+        contracts = ["0x00"]
+        [input.filter]
+        from_block = 10
+        "#,
+    );
+    assert!(errors.is_empty(), "{:?}", errors);
+
+    let errors = check(
+        r#"
+        [input]
+        rpc_url = "http://localhost:8545"
+        max_thread = "2"
+        contracts = "0x00"
+        [input.filter]
+        from_block = -1.5
+        "#,
+    );
+    assert_eq!(
+        vec![
+            "input.rpc_uri: missing required key",
+            "input.max_thread: expects Integer but got String",
+            "input.contracts: expects Array but got String",
+            "input.filter.from_block: expects Integer but got Number",
+            "input.rpc_url: unknown key",
+        ],
+        errors.problems()
+    );
+    let err = errors.into_result(Some(())).unwrap_err();
+    assert!(err
+        .get_msg()
+        .starts_with("found 5 problem(s) in config:\n  input.rpc_uri"));
+
+    let errors = check("[input]\nrpc_uri = \"\"\nmax_thread = 5000000000");
+    assert_eq!(
+        vec![
+            "input.max_thread: 5000000000 is out of range for u32",
+            "input.filter.from_block: missing required key",
+        ],
+        errors.problems()
+    );
+}
@@ -0,0 +1,195 @@
+use std::path::{Path, PathBuf};
+
+use crate::{Error, Result};
+
+/// 指定配置文件路径的环境变量
+pub const CONFIG_ENV: &str = "PRODUCER_CONFIG";
+/// 在目录中查找的配置文件名，同一目录中按顺序使用第一个存在的文件，格式由扩展名决定
+pub const CONFIG_FILES: [&str; 4] = [
+    "producer.toml",
+    "producer.yaml",
+    "producer.yml",
+    "producer.json",
+];
+/// 系统配置目录
+pub const SYSTEM_DIR: &str = "/etc/producer";
+
+/// 配置文件的候选位置，按顺序使用第一个存在的文件
+///
+/// `--config` 参数优先于 `PRODUCER_CONFIG` 环境变量，指定的文件必须存在，不再查找其他位置；
+/// 都没有指定时依次查找当前目录、执行文件目录、`/etc/producer`，与编译模式无关。
+#[derive(Debug, Clone, Default)]
+pub struct ConfigPaths {
+    /// `--config` 参数或环境变量指定的文件
+    explicit: Option<(&'static str, PathBuf)>,
+    candidates: Vec<(&'static str, PathBuf)>,
+}
+
+impl ConfigPaths {
+    /// 使用当前进程的环境变量、当前目录与执行文件目录
+    pub fn new(flag: Option<&str>) -> Self {
+        let exe_dir = std::env::current_exe()
+            .ok()
+            .and_then(|exe| exe.parent().map(Path::to_path_buf));
+        Self::with(
+            flag.map(PathBuf::from),
+            std::env::var_os(CONFIG_ENV).map(PathBuf::from),
+            std::env::current_dir().ok(),
+            exe_dir,
+        )
+    }
+
+    pub fn with(
+        flag: Option<PathBuf>,
+        env: Option<PathBuf>,
+        current_dir: Option<PathBuf>,
+        exe_dir: Option<PathBuf>,
+    ) -> Self {
+        let explicit = flag.map(|flag| ("--config", flag)).or_else(|| {
+            env.filter(|env| !env.as_os_str().is_empty())
+                .map(|env| (CONFIG_ENV, env))
+        });
+        let dirs = [
+            current_dir.map(|dir| ("current directory", dir)),
+            exe_dir.map(|dir| ("executable directory", dir)),
+            Some(("system directory", PathBuf::from(SYSTEM_DIR))),
+        ];
+        let candidates = dirs
+            .into_iter()
+            .flatten()
+            .flat_map(|(source, dir)| {
+                CONFIG_FILES
+                    .iter()
+                    .map(move |file| (source, dir.join(file)))
+            })
+            .collect();
+        Self {
+            explicit,
+            candidates,
+        }
+    }
+
+    /// 没有指定配置文件时查找的位置
+    pub fn candidates(&self) -> &[(&'static str, PathBuf)] {
+        &self.candidates
+    }
+
+    /// 返回指定的或第一个存在的配置文件，指定的文件不存在时返回 `Error::not_found`，
+    /// 查找不到时列出所有尝试过的路径
+    pub fn find(&self) -> Result<PathBuf> {
+        if let Some((source, path)) = &self.explicit {
+            if path.is_file() {
+                return Ok(path.clone());
+            }
+            return Err(Error::not_found(&format!(
+                "config file {} given by {} does not exist",
+                path.display(),
+                source
+            )));
+        }
+        if let Some((_, path)) = self.candidates.iter().find(|(_, path)| path.is_file()) {
+            return Ok(path.clone());
+        }
+        let tried: Vec<String> = self
+            .candidates
+            .iter()
+            .map(|(source, path)| format!("{}: {}", source, path.display()))
+            .collect();
+        Err(Error::not_found(&format!(
+            "no config file found, tried:\n  {}",
+            tried.join("\n  ")
+        )))
+    }
+}
+
+/// 按 [`ConfigPaths`] 的顺序查找配置文件，`flag` 为 `--config` 参数的值
+pub fn find_path(flag: Option<&str>) -> Result<PathBuf> {
+    ConfigPaths::new(flag).find()
+}
+
+#[test]
+fn test_find_path() {
+    use super::Config;
+
+    let dir = std::env::temp_dir().join("producer_test_find_path");
+    let cwd = dir.join("cwd");
+    let exe = dir.join("exe");
+    std::fs::create_dir_all(&cwd).unwrap();
+    std::fs::create_dir_all(&exe).unwrap();
+    std::fs::write(exe.join(CONFIG_FILES[0]), "").unwrap();
+    let _ = std::fs::remove_file(cwd.join(CONFIG_FILES[0]));
+
+    let paths = ConfigPaths::with(
+        None,
+        Some(PathBuf::new()),
+        Some(cwd.clone()),
+        Some(exe.clone()),
+    );
+    assert_eq!(exe.join(CONFIG_FILES[0]), paths.find().unwrap());
+
+    std::fs::write(cwd.join(CONFIG_FILES[0]), "").unwrap();
+    assert_eq!(cwd.join(CONFIG_FILES[0]), paths.find().unwrap());
+
+    let paths = ConfigPaths::with(
+        Some(exe.join(CONFIG_FILES[0])),
+        Some(cwd.join(CONFIG_FILES[0])),
+        None,
+        None,
+    );
+    assert_eq!(exe.join(CONFIG_FILES[0]), paths.find().unwrap());
+
+    // 指定的文件不存在时不查找其他位置
+    let paths = ConfigPaths::with(
+        Some(dir.join("missing.toml")),
+        None,
+        Some(cwd.clone()),
+        Some(exe.clone()),
+    );
+    let err = paths.find().unwrap_err();
+    assert_eq!(Error::not_found("").get_code(), err.get_code());
+    assert_eq!(
+        format!(
+            "config file {} given by --config does not exist",
+            dir.join("missing.toml").display()
+        ),
+        err.get_msg()
+    );
+    let paths = ConfigPaths::with(None, Some(dir.join("env.toml")), Some(cwd.clone()), None);
+    assert_eq!(
+        format!(
+            "config file {} given by PRODUCER_CONFIG does not exist",
+            dir.join("env.toml").display()
+        ),
+        paths.find().unwrap_err().get_msg()
+    );
+
+    // 同一目录中 TOML 优先，其他格式按扩展名识别
+    let yaml = dir.join("yaml");
+    std::fs::create_dir_all(&yaml).unwrap();
+    let _ = std::fs::remove_file(yaml.join("producer.toml"));
+    std::fs::write(yaml.join("producer.yml"), "buffer_size: 10\n").unwrap();
+    std::fs::write(yaml.join("producer.json"), "{}").unwrap();
+    let paths = ConfigPaths::with(None, None, Some(yaml.clone()), None);
+    let path = paths.find().unwrap();
+    assert_eq!(yaml.join("producer.yml"), path);
+    let config = super::FileConfig::from_path(&path).unwrap();
+    assert_eq!(10, config.get_value::<_, i32>("buffer_size").unwrap());
+    std::fs::write(yaml.join("producer.toml"), "").unwrap();
+    assert_eq!(yaml.join("producer.toml"), paths.find().unwrap());
+
+    let paths = ConfigPaths::with(None, None, Some(dir.clone()), None);
+    let err = paths.find().unwrap_err();
+    let tried: Vec<String> = CONFIG_FILES
+        .iter()
+        .map(|file| format!("current directory: {}", dir.join(file).display()))
+        .chain(
+            CONFIG_FILES
+                .iter()
+                .map(|file| format!("system directory: /etc/producer/{}", file)),
+        )
+        .collect();
+    assert_eq!(
+        format!("no config file found, tried:\n  {}", tried.join("\n  ")),
+        err.get_msg()
+    );
+}
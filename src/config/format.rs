@@ -0,0 +1,177 @@
+use std::path::Path;
+
+use toml::value::Table;
+use toml::Value as TomlValue;
+
+use crate::{Error, Result};
+
+/// 配置文件格式，YAML 与 JSON 解析后转为与 TOML 相同的表，使用相同的键查找规则
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Toml,
+    Yaml,
+    Json,
+}
+
+impl Format {
+    /// 根据扩展名判断格式，无法识别时返回 `None`
+    pub fn from_extension<P: AsRef<Path>>(path: P) -> Option<Self> {
+        let extension = path.as_ref().extension()?.to_str()?.to_lowercase();
+        match extension.as_str() {
+            "toml" => Some(Format::Toml),
+            "yaml" | "yml" => Some(Format::Yaml),
+            "json" => Some(Format::Json),
+            _ => None,
+        }
+    }
+
+    /// 根据扩展名判断格式，无法识别时返回错误
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        Self::from_extension(path).ok_or_else(|| {
+            Error::invalid_param(&format!(
+                "can't detect config format of {}, expects .toml, .yaml, .yml or .json",
+                path.display()
+            ))
+        })
+    }
+
+    pub fn parse(&self, text: &str) -> Result<Table> {
+        let value = match self {
+            Format::Toml => text
+                .parse::<TomlValue>()
+                .map(Some)
+                .map_err(|err| err.to_string()),
+            // serde_yaml 无法解析空文件，与 `~` 一样视为空表
+            Format::Yaml if text.trim().is_empty() => Ok(None),
+            Format::Yaml => serde_yaml::from_str::<serde_yaml::Value>(text)
+                .map_err(|err| err.to_string())
+                .and_then(|value| from_yaml(value, "")),
+            Format::Json => serde_json::from_str::<serde_json::Value>(text)
+                .map_err(|err| err.to_string())
+                .and_then(|value| from_json(value, "")),
+        };
+        match value {
+            Ok(Some(TomlValue::Table(table))) => Ok(table),
+            Ok(None) => Ok(Table::new()),
+            Ok(Some(_)) => Err(Error::invalid_data("config file must be a table")),
+            Err(err) => Err(Error::invalid_data(&format!(
+                "config file is not valid {} - {}",
+                self, err
+            ))),
+        }
+    }
+}
+
+impl std::fmt::Display for Format {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Format::Toml => write!(f, "TOML"),
+            Format::Yaml => write!(f, "YAML"),
+            Format::Json => write!(f, "JSON"),
+        }
+    }
+}
+
+fn child(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_owned()
+    } else {
+        format!("{}.{}", path, key)
+    }
+}
+
+/// 数组中不能有 null，表中值为 null 的键视为不存在
+fn array<T, F>(values: Vec<T>, path: &str, convert: F) -> std::result::Result<TomlValue, String>
+where
+    F: Fn(T, &str) -> std::result::Result<Option<TomlValue>, String>,
+{
+    values
+        .into_iter()
+        .enumerate()
+        .map(|(index, value)| {
+            let path = format!("{}[{}]", path, index);
+            convert(value, &path)?
+                .ok_or_else(|| format!("{} is null, arrays can't contain null", path))
+        })
+        .collect::<std::result::Result<Vec<TomlValue>, String>>()
+        .map(TomlValue::Array)
+}
+
+/// 整数超出 i64 范围时报错，不转为浮点数以免丢失精度
+fn number<N: std::fmt::Display>(
+    integer: Option<i64>,
+    float: Option<f64>,
+    val: &N,
+    path: &str,
+) -> std::result::Result<TomlValue, String> {
+    match (integer, float) {
+        (Some(val), _) => Ok(TomlValue::Integer(val)),
+        (None, Some(val)) => Ok(TomlValue::Float(val)),
+        (None, None) => Err(format!("{} is out of range for i64 at {}", val, path)),
+    }
+}
+
+fn from_yaml(
+    value: serde_yaml::Value,
+    path: &str,
+) -> std::result::Result<Option<TomlValue>, String> {
+    use serde_yaml::Value as YamlValue;
+    let value = match value {
+        YamlValue::Null => return Ok(None),
+        YamlValue::Bool(val) => TomlValue::Boolean(val),
+        YamlValue::Number(val) => number(
+            val.as_i64(),
+            val.as_f64().filter(|_| val.is_f64()),
+            &val,
+            path,
+        )?,
+        YamlValue::String(val) => TomlValue::String(val),
+        YamlValue::Sequence(values) => array(values, path, from_yaml)?,
+        YamlValue::Mapping(mapping) => {
+            let mut table = Table::new();
+            for (key, value) in mapping {
+                let key = match key {
+                    YamlValue::String(key) => key,
+                    YamlValue::Number(key) => key.to_string(),
+                    YamlValue::Bool(key) => key.to_string(),
+                    key => return Err(format!("unsupported key {:?} at {}", key, path)),
+                };
+                if let Some(value) = from_yaml(value, &child(path, &key))? {
+                    table.insert(key, value);
+                }
+            }
+            TomlValue::Table(table)
+        }
+    };
+    Ok(Some(value))
+}
+
+fn from_json(
+    value: serde_json::Value,
+    path: &str,
+) -> std::result::Result<Option<TomlValue>, String> {
+    use serde_json::Value as JsonValue;
+    let value = match value {
+        JsonValue::Null => return Ok(None),
+        JsonValue::Bool(val) => TomlValue::Boolean(val),
+        JsonValue::Number(val) => number(
+            val.as_i64(),
+            val.as_f64().filter(|_| val.is_f64()),
+            &val,
+            path,
+        )?,
+        JsonValue::String(val) => TomlValue::String(val),
+        JsonValue::Array(values) => array(values, path, from_json)?,
+        JsonValue::Object(object) => {
+            let mut table = Table::new();
+            for (key, value) in object {
+                if let Some(value) = from_json(value, &child(path, &key))? {
+                    table.insert(key, value);
+                }
+            }
+            TomlValue::Table(table)
+        }
+    };
+    Ok(Some(value))
+}
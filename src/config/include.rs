@@ -0,0 +1,153 @@
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use toml::value::Table;
+use toml::Value as TomlValue;
+
+use super::format::Format;
+use crate::{Error, Result};
+
+/// 引用其他配置文件的键
+pub const INCLUDE_KEY: &str = "include";
+
+/// 按 `format` 读取配置文件并合并 `include` 引用的文件
+///
+/// 引用的文件按扩展名判断格式，无法识别时与引用它的文件相同。
+pub fn load_path<P: AsRef<Path>>(path: P, format: Format) -> Result<Table> {
+    load_file(path.as_ref(), format, &mut vec![])
+}
+
+/// 合并 `include` 引用的文件，相对路径基于 `dir`
+pub fn load_includes(table: Table, dir: &Path, format: Format) -> Result<Table> {
+    merge_includes(table, dir, format, &mut vec![])
+}
+
+fn load_file(path: &Path, format: Format, stack: &mut Vec<PathBuf>) -> Result<Table> {
+    let canonical = path.canonicalize().map_err(|err| {
+        Error::new(
+            Error::from(err).get_code(),
+            &format!("can't open config file {}", path.display()),
+        )
+    })?;
+    if stack.contains(&canonical) {
+        let chain: Vec<String> = stack
+            .iter()
+            .chain(std::iter::once(&canonical))
+            .map(|path| path.display().to_string())
+            .collect();
+        return Err(Error::invalid_data(&format!(
+            "config file {} is included recursively: {}",
+            canonical.display(),
+            chain.join(" -> ")
+        )));
+    }
+
+    let mut text = String::new();
+    File::open(&canonical)?.read_to_string(&mut text)?;
+    let table = format.parse(&text).map_err(|err| {
+        Error::invalid_data(&format!("{} - {}", canonical.display(), err.get_msg()))
+    })?;
+
+    stack.push(canonical.clone());
+    let dir = canonical.parent().unwrap_or_else(|| Path::new("."));
+    let table = merge_includes(table, dir, format, stack);
+    stack.pop();
+    table
+}
+
+fn merge_includes(
+    mut table: Table,
+    dir: &Path,
+    format: Format,
+    stack: &mut Vec<PathBuf>,
+) -> Result<Table> {
+    let includes = match table.remove(INCLUDE_KEY) {
+        None => return Ok(table),
+        Some(TomlValue::String(path)) => vec![path],
+        Some(TomlValue::Array(paths)) => paths
+            .into_iter()
+            .map(|path| match path {
+                TomlValue::String(path) => Ok(path),
+                _ => Err(Error::invalid_data("include must be an array of paths")),
+            })
+            .collect::<Result<Vec<String>>>()?,
+        Some(_) => return Err(Error::invalid_data("include must be an array of paths")),
+    };
+
+    // 先合并引用的文件，当前文件的值优先
+    let mut merged = Table::new();
+    for include in includes {
+        let path = dir.join(include);
+        let format = Format::from_extension(&path).unwrap_or(format);
+        merge(&mut merged, load_file(&path, format, stack)?);
+    }
+    merge(&mut merged, table);
+    Ok(merged)
+}
+
+/// 深度合并两个表，`over` 中的值优先
+pub fn merge(base: &mut Table, over: Table) {
+    for (key, value) in over {
+        match (base.get_mut(&key), value) {
+            (Some(TomlValue::Table(base)), TomlValue::Table(over)) => merge(base, over),
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+fn write_config(dir: &Path, name: &str, toml: &str) -> PathBuf {
+    std::fs::create_dir_all(dir).unwrap();
+    let path = dir.join(name);
+    std::fs::write(&path, toml).unwrap();
+    path
+}
+
+#[test]
+fn test_include() {
+    let dir = std::env::temp_dir().join("producer_test_include");
+    write_config(
+        &dir.join("common"),
+        "input.toml",
+        r#"
+        [input]
+        rpc_uri = "http://localhost:8545"
+        max_thread = 2
+        "#,
+    );
+    let path = write_config(
+        &dir,
+        "main.toml",
+        r#"
+        include = ["common/input.toml"]
+        buffer_size = 10
+        [input]
+        max_thread = 4
+        "#,
+    );
+
+    let table = load_path(path, Format::Toml).unwrap();
+    assert!(table.get(INCLUDE_KEY).is_none());
+    let input = table["input"].as_table().unwrap();
+    assert_eq!("http://localhost:8545", input["rpc_uri"].as_str().unwrap());
+    assert_eq!(4, input["max_thread"].as_integer().unwrap());
+    assert_eq!(10, table["buffer_size"].as_integer().unwrap());
+}
+
+#[test]
+fn test_include_cycle() {
+    let dir = std::env::temp_dir().join("producer_test_include_cycle");
+    write_config(&dir, "a.toml", r#"include = ["b.toml"]"#);
+    let path = write_config(&dir, "b.toml", r#"include = "a.toml""#);
+
+    let err = load_path(path, Format::Toml).unwrap_err();
+    assert_eq!(Error::invalid_data("").get_code(), err.get_code());
+    assert!(err.get_msg().contains("b.toml is included recursively"));
+
+    let path = write_config(&dir, "c.toml", r#"include = ["missing.toml"]"#);
+    let err = load_path(path, Format::Toml).unwrap_err();
+    assert!(err.get_msg().contains("missing.toml"));
+}
@@ -0,0 +1,184 @@
+use toml::value::Table;
+use toml::Value as TomlValue;
+
+use super::key;
+use crate::{Error, Result};
+
+/// 替换配置中字符串里的 `${name}`
+///
+/// `name` 优先作为配置键（如 `${input.rpc_uri}`）查找，找不到时读取同名环境变量，
+/// `$${` 表示字面量 `${`。若整个字符串只有一个引用，保留被引用值的类型。
+pub fn interpolate<E: Fn(&str) -> Option<String>>(table: &Table, env: &E) -> Result<Table> {
+    let resolver = Resolver { root: table, env };
+    resolver.table(table, "", &mut vec![])
+}
+
+struct Resolver<'a, E> {
+    root: &'a Table,
+    env: &'a E,
+}
+
+impl<'a, E: Fn(&str) -> Option<String>> Resolver<'a, E> {
+    fn table(&self, table: &Table, path: &str, stack: &mut Vec<String>) -> Result<Table> {
+        let mut resolved = Table::new();
+        for (key, value) in table {
+            let path = if path.is_empty() {
+                key.clone()
+            } else {
+                format!("{}.{}", path, key)
+            };
+            stack.push(path.clone());
+            let value = self.value(value, &path, stack);
+            stack.pop();
+            resolved.insert(key.clone(), value?);
+        }
+        Ok(resolved)
+    }
+
+    fn value(&self, value: &TomlValue, path: &str, stack: &mut Vec<String>) -> Result<TomlValue> {
+        let value = match value {
+            TomlValue::String(val) => self.string(val, stack)?,
+            TomlValue::Array(array) => TomlValue::Array(
+                array
+                    .iter()
+                    .map(|val| self.value(val, path, stack))
+                    .collect::<Result<Vec<TomlValue>>>()?,
+            ),
+            TomlValue::Table(table) => TomlValue::Table(self.table(table, path, stack)?),
+            value => value.clone(),
+        };
+        Ok(value)
+    }
+
+    fn string(&self, val: &str, stack: &mut Vec<String>) -> Result<TomlValue> {
+        // 整个字符串只有一个引用时，直接使用被引用的值
+        if let Some(name) = val.strip_prefix("${").and_then(|s| s.strip_suffix('}')) {
+            if !name.contains(['$', '{', '}']) {
+                return self.lookup(name, stack);
+            }
+        }
+
+        let mut resolved = String::new();
+        let mut rest = val;
+        while let Some(index) = rest.find('$') {
+            resolved.push_str(&rest[..index]);
+            rest = &rest[index..];
+            if let Some(next) = rest.strip_prefix("$${") {
+                resolved.push_str("${");
+                rest = next;
+            } else if let Some(next) = rest.strip_prefix("${") {
+                let end = next.find('}').ok_or_else(|| {
+                    Error::invalid_data(&format!("unterminated ${{ in config value {:?}", val))
+                })?;
+                match self.lookup(&next[..end], stack)? {
+                    TomlValue::String(val) => resolved.push_str(&val),
+                    TomlValue::Table(_) | TomlValue::Array(_) => {
+                        return Err(Error::invalid_data(&format!(
+                            "can't interpolate table or array ${{{}}} into {:?}",
+                            &next[..end],
+                            val
+                        )))
+                    }
+                    val => resolved.push_str(&val.to_string()),
+                }
+                rest = &next[end + 1..];
+            } else {
+                resolved.push('$');
+                rest = &rest[1..];
+            }
+        }
+        resolved.push_str(rest);
+        Ok(TomlValue::String(resolved))
+    }
+
+    fn lookup(&self, name: &str, stack: &mut Vec<String>) -> Result<TomlValue> {
+        let name = name.trim();
+        if stack.iter().any(|key| key == name) {
+            return Err(Error::invalid_data(&format!(
+                "config interpolation cycle: {} -> {}",
+                stack.join(" -> "),
+                name
+            )));
+        }
+
+        if let Some(value) = get_toml_value(self.root, name) {
+            stack.push(name.to_owned());
+            let value = self.value(value, name, stack);
+            stack.pop();
+            return value;
+        }
+        (self.env)(name).map(TomlValue::String).ok_or_else(|| {
+            Error::invalid_data(&format!(
+                "${{{}}} is neither a config key nor an environment variable",
+                name
+            ))
+        })
+    }
+}
+
+fn get_toml_value<'a>(table: &'a Table, key: &str) -> Option<&'a TomlValue> {
+    key::lookup(table, &key::parse(key).ok()?)
+}
+
+#[cfg(test)]
+fn resolve(toml: &str) -> Result<Table> {
+    let table = super::Format::Toml.parse(toml).unwrap();
+    interpolate(&table, &|name| match name {
+        "RPC_KEY" => Some("secret".to_owned()),
+        _ => None,
+    })
+}
+
+#[test]
+fn test_interpolate() {
+    let table = resolve(
+        r#"
+        chain = "mainnet"
+        [common]
+        port = 8545
+        host = "node.${chain}.io"
+        [input]
+        rpc_uri = "https://${common.host}:${common.port}/${RPC_KEY}"
+        port = "${common.port}"
+        price = "$${not_a_key} costs $5"
+        urls = ["${chain}", "${ chain }"]
+        "#,
+    )
+    .unwrap();
+    let input = table["input"].as_table().unwrap();
+    assert_eq!(
+        "https://node.mainnet.io:8545/secret",
+        input["rpc_uri"].as_str().unwrap()
+    );
+    assert_eq!(8545, input["port"].as_integer().unwrap());
+    assert_eq!("${not_a_key} costs $5", input["price"].as_str().unwrap());
+    assert_eq!(
+        vec!["mainnet", "mainnet"],
+        input["urls"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|val| val.as_str().unwrap())
+            .collect::<Vec<&str>>()
+    );
+}
+
+#[test]
+fn test_interpolate_error() {
+    let err = resolve(r#"a = "${MISSING}""#).unwrap_err();
+    assert!(err.get_msg().contains("MISSING"));
+
+    let err = resolve(
+        r#"
+        a = "${b}"
+        b = "x${c}"
+        c = "${a}"
+        "#,
+    )
+    .unwrap_err();
+    assert_eq!(Error::invalid_data("").get_code(), err.get_code());
+    assert!(err.get_msg().contains("cycle: a -> b -> c -> a"));
+
+    assert!(resolve(r#"a = "${b""#).is_err());
+    assert!(resolve("a = \"x${b}\"\n[b]\nc = 1").is_err());
+}
@@ -0,0 +1,208 @@
+use std::fmt::Display;
+
+use toml::value::Table;
+use toml::Value as TomlValue;
+
+use crate::{Error, Result};
+
+/// 键路径中的一段
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Segment {
+    /// 表中的键，如 `input` 或 `"app.kubernetes.io/name"`
+    Key(String),
+    /// 数组下标，如 `[1]` 或 `.1`
+    Index(usize),
+}
+
+impl Display for Segment {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Segment::Index(index) => write!(f, "[{}]", index),
+            Segment::Key(key) if is_bare(key) => write!(f, "{}", key),
+            Segment::Key(key) => write!(f, "{:?}", key),
+        }
+    }
+}
+
+/// 不需要加引号的键名
+fn is_bare(key: &str) -> bool {
+    !key.is_empty()
+        && !key.bytes().all(|b| b.is_ascii_digit())
+        && !key.contains(['.', '[', ']', '"', '\'', ' '])
+}
+
+/// 解析键路径
+///
+/// 各段用 `.` 分隔，`[n]` 或纯数字的段表示数组下标，含有 `.` 等字符的键名用 `"..."` 或 `'...'` 括起，
+/// 如 `input.contracts[1].address`、`outputs.0.path`、`labels."app.kubernetes.io/name"`。
+/// 空字符串表示根表。
+pub fn parse(key: &str) -> Result<Vec<Segment>> {
+    let invalid =
+        |msg: &str| Error::invalid_param(&format!("invalid config key {} - {}", key, msg));
+    let mut segments = vec![];
+    let mut chars = key.chars().peekable();
+    // 是否需要一个新的段，开头或 `.` 之后
+    let mut expect_segment = !key.is_empty();
+    while let Some(&c) = chars.peek() {
+        match c {
+            '.' if !expect_segment => {
+                chars.next();
+                expect_segment = true;
+            }
+            '[' => {
+                chars.next();
+                let mut index = String::new();
+                loop {
+                    match chars.next() {
+                        Some(']') => break,
+                        Some(c) if c.is_ascii_digit() => index.push(c),
+                        _ => return Err(invalid("expects [index]")),
+                    }
+                }
+                let index = index.parse().map_err(|_| invalid("expects [index]"))?;
+                segments.push(Segment::Index(index));
+                expect_segment = false;
+            }
+            '"' | '\'' if expect_segment => {
+                chars.next();
+                let mut name = String::new();
+                loop {
+                    match chars.next() {
+                        Some(end) if end == c => break,
+                        Some('\\') => match chars.next() {
+                            Some(escaped) => name.push(escaped),
+                            None => return Err(invalid("unterminated quote")),
+                        },
+                        Some(other) => name.push(other),
+                        None => return Err(invalid("unterminated quote")),
+                    }
+                }
+                segments.push(Segment::Key(name));
+                expect_segment = false;
+            }
+            _ if expect_segment => {
+                let mut name = String::new();
+                while let Some(&c) = chars.peek() {
+                    if matches!(c, '.' | '[' | ']' | '"' | '\'') {
+                        break;
+                    }
+                    name.push(c);
+                    chars.next();
+                }
+                let name = name.trim();
+                if name.is_empty() {
+                    return Err(invalid("empty segment"));
+                }
+                segments.push(match name.parse() {
+                    Ok(index) if name.bytes().all(|b| b.is_ascii_digit()) => Segment::Index(index),
+                    _ => Segment::Key(name.to_owned()),
+                });
+                expect_segment = false;
+            }
+            _ => return Err(invalid(&format!("unexpected {:?}", c))),
+        }
+    }
+    if expect_segment {
+        return Err(invalid("empty segment"));
+    }
+    Ok(segments)
+}
+
+/// 将各段组合为规范的键路径，`parse` 的逆操作
+pub fn render(segments: &[Segment]) -> String {
+    let mut key = String::new();
+    for segment in segments {
+        if !key.is_empty() && matches!(segment, Segment::Key(_)) {
+            key.push('.');
+        }
+        key.push_str(&segment.to_string());
+    }
+    key
+}
+
+/// 规范化键路径，无法解析时返回错误
+pub fn normalize(key: &str) -> Result<String> {
+    parse(key).map(|segments| render(&segments))
+}
+
+/// 在 `prefix` 之后追加一个键名，键名含有特殊字符时加引号
+pub fn join(prefix: &str, name: &str) -> String {
+    let segment = Segment::Key(name.to_owned());
+    if prefix.is_empty() {
+        segment.to_string()
+    } else {
+        format!("{}.{}", prefix, segment)
+    }
+}
+
+/// 在 `prefix` 之后追加另一个键路径
+pub fn join_path(prefix: &str, key: &str) -> String {
+    if prefix.is_empty() {
+        key.to_owned()
+    } else if key.is_empty() {
+        prefix.to_owned()
+    } else if key.starts_with('[') {
+        format!("{}{}", prefix, key)
+    } else {
+        format!("{}.{}", prefix, key)
+    }
+}
+
+/// 按路径查找值，下标也可以用于键名为数字的表
+pub fn lookup<'a>(table: &'a Table, segments: &[Segment]) -> Option<&'a TomlValue> {
+    let (first, rest) = segments.split_first()?;
+    let mut value = match first {
+        Segment::Key(key) => table.get(key)?,
+        Segment::Index(index) => table.get(&index.to_string())?,
+    };
+    for segment in rest {
+        value = match (value, segment) {
+            (TomlValue::Table(table), Segment::Key(key)) => table.get(key)?,
+            (TomlValue::Table(table), Segment::Index(index)) => table.get(&index.to_string())?,
+            (TomlValue::Array(array), Segment::Index(index)) => array.get(*index)?,
+            _ => return None,
+        };
+    }
+    Some(value)
+}
+
+#[test]
+fn test_parse_key() {
+    use Segment::*;
+    assert_eq!(Vec::<Segment>::new(), parse("").unwrap());
+    assert_eq!(
+        vec![
+            Key("input".to_owned()),
+            Key("contracts".to_owned()),
+            Index(1),
+            Key("address".to_owned())
+        ],
+        parse("input.contracts[1].address").unwrap()
+    );
+    assert_eq!(
+        vec![Key("outputs".to_owned()), Index(0), Key("path".to_owned())],
+        parse("outputs.0.path").unwrap()
+    );
+    assert_eq!(
+        vec![
+            Key("labels".to_owned()),
+            Key("app.kubernetes.io/name".to_owned()),
+            Index(2),
+            Index(3)
+        ],
+        parse(r#"labels."app.kubernetes.io/name"[2][3]"#).unwrap()
+    );
+    assert_eq!(vec![Key("it's".to_owned())], parse(r#"'it\'s'"#).unwrap());
+
+    for key in ["a.", ".a", "a..b", "a[", "a[x]", "a]", "\"a", "a\"b\""] {
+        assert!(parse(key).is_err(), "{}", key);
+    }
+
+    assert_eq!("outputs[0].path", normalize("outputs.0.path").unwrap());
+    assert_eq!(
+        r#"labels."app.kubernetes.io/name""#,
+        normalize("labels.'app.kubernetes.io/name'").unwrap()
+    );
+    assert_eq!(r#"a."0""#, join("a", "0"));
+    assert_eq!("a[0]", join_path("a", "[0]"));
+}
@@ -0,0 +1,258 @@
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+
+use super::key::{self, Segment};
+use super::{Config, CONFIG_ENV, KEYSTORE_PASSWORD_ENV};
+use crate::{DataType, Error, Result, Value};
+
+/// 环境变量覆盖配置时使用的前缀
+pub const ENV_PREFIX: &str = "PRODUCER_";
+
+/// 分层配置，按 `--set` 命令行参数、`PRODUCER_` 环境变量、配置文件的优先级查找键
+///
+/// 环境变量名去掉前缀后转为小写，`__` 表示 `.`，如 `PRODUCER_INPUT__RPC_URI` 对应 `input.rpc_uri`
+pub struct LayeredConfig<C: Config> {
+    inner: C,
+    env: BTreeMap<String, String>,
+    args: BTreeMap<String, String>,
+}
+
+impl<C: Config> LayeredConfig<C> {
+    pub fn new(inner: C) -> Self {
+        Self {
+            inner,
+            env: BTreeMap::new(),
+            args: BTreeMap::new(),
+        }
+    }
+
+    /// 读取当前进程的环境变量
+    pub fn with_env(self) -> Self {
+        self.with_vars(std::env::vars())
+    }
+
+    /// 读取指定的环境变量，忽略没有 `PRODUCER_` 前缀的变量
+    pub fn with_vars<I: IntoIterator<Item = (String, String)>>(mut self, vars: I) -> Self {
+        for (name, value) in vars {
+            // 配置文件路径与 keystore 密码不是配置项
+            if name == CONFIG_ENV || name == KEYSTORE_PASSWORD_ENV {
+                continue;
+            }
+            if let Some(name) = name.strip_prefix(ENV_PREFIX) {
+                if name.is_empty() {
+                    continue;
+                }
+                let key = name.to_lowercase().replace("__", ".");
+                let key = key::normalize(&key).unwrap_or(key);
+                self.env.insert(key, parse_override(&value));
+            }
+        }
+        self
+    }
+
+    /// 读取 `--set` 参数的值，每一项的格式为 `key=value`
+    pub fn with_sets<I: IntoIterator<Item = S>, S: AsRef<str>>(mut self, sets: I) -> Result<Self> {
+        for set in sets {
+            let set = set.as_ref();
+            let (key, value) = set
+                .split_once('=')
+                .filter(|(key, _)| !key.trim().is_empty())
+                .ok_or_else(|| {
+                    Error::invalid_param(&format!("--set expects key=value but got {}", set))
+                })?;
+            self.args
+                .insert(key::normalize(key.trim())?, parse_override(value));
+        }
+        Ok(self)
+    }
+
+    pub fn inner(&self) -> &C {
+        &self.inner
+    }
+
+    fn get_override(&self, key: &str) -> Option<&str> {
+        let key = key::normalize(key).ok()?;
+        self.args
+            .get(&key)
+            .or_else(|| self.env.get(&key))
+            .map(String::as_str)
+    }
+}
+
+impl<C: Config> Config for LayeredConfig<C> {
+    fn get_value<K: Into<String>, T: TryFrom<Value, Error = Error>>(&self, key: K) -> Result<T> {
+        let key = key.into();
+        match self.get_override(&key) {
+            Some(value) => override_value(value),
+            None => self.inner.get_value(key),
+        }
+    }
+
+    fn get_keys<K: Into<String>>(&self, key: K) -> Result<Vec<String>> {
+        let key = key.into();
+        let prefix = key::parse(&key)?;
+        // 覆盖值中位于该表下的键，取前缀之后的第一段
+        let mut overrides: Vec<String> = self
+            .args
+            .keys()
+            .chain(self.env.keys())
+            .filter_map(|name| {
+                let segments = key::parse(name).ok()?;
+                match segments.get(prefix.len()) {
+                    Some(Segment::Key(name)) if segments.starts_with(&prefix) => Some(name.clone()),
+                    _ => None,
+                }
+            })
+            .collect();
+
+        let mut keys = match self.inner.get_keys(key) {
+            Ok(keys) => keys,
+            Err(err) if overrides.is_empty() => return Err(err),
+            Err(_) => vec![],
+        };
+        overrides.retain(|name| !keys.contains(name));
+        overrides.sort();
+        overrides.dedup();
+        keys.extend(overrides);
+        Ok(keys)
+    }
+}
+
+/// 覆盖值保存为字符串，引号包围的值去掉引号，如 `'bee'`；其他值原样保存，
+/// 避免 `0x465a…` 被解析为字节数组、`007` 被解析为整数
+fn parse_override(value: &str) -> String {
+    match value.parse::<Value>() {
+        Ok(Value::String(value)) => value,
+        _ => value.to_owned(),
+    }
+}
+
+/// 按读取的类型转换覆盖值：先作为字符串，再依次尝试整数、浮点数、布尔值与数组等字面量
+fn override_value<T: TryFrom<Value, Error = Error>>(raw: &str) -> Result<T> {
+    let value = Value::String(raw.to_owned());
+    let err = match T::try_from(value.clone()) {
+        Ok(value) => return Ok(value),
+        Err(err) => err,
+    };
+    for data_type in [DataType::Integer, DataType::Number, DataType::Boolean] {
+        if let Ok(value) = value.clone().cast(data_type).and_then(T::try_from) {
+            return Ok(value);
+        }
+    }
+    raw.parse::<Value>().and_then(T::try_from).map_err(|_| err)
+}
+
+#[cfg(test)]
+fn layered() -> LayeredConfig<super::TomlConfig> {
+    let config = super::TomlConfig::from_string(
+        r#"
+        buffer_size = 10
+        [input]
+        rpc_uri = "http://localhost:8545"
+        max_thread = 2
+        "#,
+    )
+    .unwrap();
+    LayeredConfig::new(config).with_vars(vec![
+        (
+            "PRODUCER_INPUT__RPC_URI".to_owned(),
+            "https://mainnet".to_owned(),
+        ),
+        ("PRODUCER_INPUT__MAX_THREAD".to_owned(), "4".to_owned()),
+        ("PRODUCER_OUTPUT__MAX_THREAD".to_owned(), "1".to_owned()),
+        ("PRODUCER_BUFFER_SIZE".to_owned(), "20".to_owned()),
+        ("HOME".to_owned(), "/root".to_owned()),
+        ("PRODUCER_CONFIG".to_owned(), "bee.toml".to_owned()),
+    ])
+}
+
+#[test]
+fn test_env_override() {
+    let config = layered();
+    let rpc_uri: String = config.get_value("input.rpc_uri").unwrap();
+    assert_eq!("https://mainnet", rpc_uri);
+    assert_eq!(4, config.get_value::<_, i32>("input.max_thread").unwrap());
+    assert_eq!(20, config.get_value::<_, i32>("buffer_size").unwrap());
+    assert_eq!(1, config.get_value::<_, i32>("output.max_thread").unwrap());
+    assert!(config.get_value::<_, String>("home").is_err());
+    assert!(config.get_value::<_, String>("config").is_err());
+}
+
+#[test]
+fn test_set_override() {
+    let config = layered()
+        .with_sets(vec!["input.rpc_uri=https://goerli", "input.name = 'bee'"])
+        .unwrap();
+    let rpc_uri: String = config.get_value("input.rpc_uri").unwrap();
+    assert_eq!("https://goerli", rpc_uri);
+    let name: String = config.get_value("input.name").unwrap();
+    assert_eq!("bee", name);
+    assert_eq!(4, config.get_value::<_, i32>("input.max_thread").unwrap());
+
+    assert!(layered().with_sets(vec!["input.rpc_uri"]).is_err());
+    assert!(layered().with_sets(vec!["=10"]).is_err());
+    assert!(layered().with_sets(vec!["input.[0=10"]).is_err());
+
+    let config = layered()
+        .with_sets(vec!["input.contracts[1]='0x02'", "labels.'app.name'=bee"])
+        .unwrap();
+    let contract: String = config.get_value("input.contracts.1").unwrap();
+    assert_eq!("0x02", contract);
+    let name: String = config.get_value(r#"labels."app.name""#).unwrap();
+    assert_eq!("bee", name);
+    assert_eq!(vec!["app.name"], config.get_keys("labels").unwrap());
+}
+
+#[test]
+fn test_layered_keys() {
+    let config = layered().with_sets(vec!["input.name=bee"]).unwrap();
+    assert_eq!(
+        vec!["buffer_size", "input", "output"],
+        config.get_keys("").unwrap()
+    );
+    assert_eq!(
+        vec!["max_thread", "rpc_uri", "name"],
+        config.get_keys("input").unwrap()
+    );
+    assert_eq!(vec!["max_thread"], config.get_keys("output").unwrap());
+    assert!(config.get_keys("decoder").is_err());
+}
+
+#[test]
+fn test_override_strings() {
+    let address = "0x465a4A8DAA955B837957230385AC4A9997aa9d27";
+    let config = layered()
+        .with_sets(vec![
+            format!("input.addr={}", address),
+            "input.zip=007".to_owned(),
+        ])
+        .unwrap()
+        .with_vars(vec![(
+            "PRODUCER_INPUT__X".to_owned(),
+            "0x0\u{e9}0".to_owned(),
+        )]);
+    assert_eq!(
+        address,
+        config.get_value::<_, String>("input.addr").unwrap()
+    );
+    assert_eq!("007", config.get_value::<_, String>("input.zip").unwrap());
+    assert_eq!(7, config.get_value::<_, u32>("input.zip").unwrap());
+    assert_eq!(
+        "0x0\u{e9}0",
+        config.get_value::<_, String>("input.x").unwrap()
+    );
+
+    // `Section` 按读取的类型转换覆盖值
+    let mut errors = super::ConfigErrors::new();
+    let mut section = super::Section::new(&config, "input", &mut errors);
+    assert_eq!(Some(address.to_owned()), section.optional::<String>("addr"));
+    assert_eq!(Some(7_u64), section.optional::<u64>("zip"));
+    assert_eq!(Some(4_usize), section.optional::<usize>("max_thread"));
+    assert_eq!(Some(7.0), section.optional::<f64>("zip"));
+    assert_eq!(None, section.optional::<bool>("addr"));
+    drop(section);
+    assert_eq!(
+        vec!["input.addr: expects Boolean but got String".to_owned()],
+        errors.problems()
+    );
+}
@@ -0,0 +1,419 @@
+mod check;
+mod discover;
+mod format;
+mod include;
+mod interpolate;
+mod key;
+mod layered;
+mod secret;
+
+use std::path::Path;
+use std::{collections::HashMap, convert::TryFrom};
+use toml::value::Table;
+use toml::Value as TomlValue;
+
+use crate::{Error, Result, Value};
+
+pub use check::{ConfigErrors, Section};
+pub use discover::{find_path, ConfigPaths, CONFIG_ENV, CONFIG_FILES};
+pub use format::Format;
+pub use layered::{LayeredConfig, ENV_PREFIX};
+pub use secret::{decrypt_keystore, Secret, KEYSTORE_PASSWORD_ENV};
+
+/// 按键路径读取配置，键路径的格式见 `key::parse`，如 `input.contracts[1]`、`labels."app.kubernetes.io/name"`
+pub trait Config {
+    fn get_value<K: Into<String>, T: TryFrom<Value, Error = Error>>(&self, key: K) -> Result<T>;
+
+    /// 获取指定表下的所有键名，空键表示根表
+    fn get_keys<K: Into<String>>(&self, key: K) -> Result<Vec<String>>;
+
+    /// 获取指定的表，返回的配置中的键相对于该表
+    fn get_section<K: Into<String>>(&self, key: K) -> Result<impl Config + '_>
+    where
+        Self: Sized,
+    {
+        let key = key::normalize(&key.into())?;
+        self.get_keys(key.as_str())?;
+        Ok(ScopedConfig { inner: self, key })
+    }
+}
+
+/// `Config::get_section` 返回的配置，读取时在键前加上表的路径
+pub struct ScopedConfig<'a, C: Config> {
+    inner: &'a C,
+    key: String,
+}
+
+impl<'a, C: Config> Config for ScopedConfig<'a, C> {
+    fn get_value<K: Into<String>, T: TryFrom<Value, Error = Error>>(&self, key: K) -> Result<T> {
+        self.inner.get_value(key::join_path(&self.key, &key.into()))
+    }
+
+    fn get_keys<K: Into<String>>(&self, key: K) -> Result<Vec<String>> {
+        self.inner.get_keys(key::join_path(&self.key, &key.into()))
+    }
+}
+
+pub trait ToValue {
+    fn to(&self) -> Value;
+}
+
+pub struct TomlConfig {
+    inner: Table,
+}
+
+impl ToValue for TomlValue {
+    fn to(&self) -> Value {
+        match &self {
+            TomlValue::String(val) => Value::String(val.clone()),
+            TomlValue::Integer(val) => Value::Integer(*val),
+            TomlValue::Float(val) => Value::Number(*val),
+            TomlValue::Boolean(val) => Value::Boolean(*val),
+            TomlValue::Datetime(val) => Value::String(val.to_string()),
+            TomlValue::Array(val) => Value::Array(val.iter().map(|val| val.to()).collect()),
+            TomlValue::Table(_val) => Value::Nil,
+        }
+    }
+}
+
+impl TomlConfig {
+    /// 读取配置文件，合并 `include` 引用的文件后替换 `${...}` 引用
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let inner = load_path(path, Format::Toml)?;
+        Ok(Self { inner })
+    }
+
+    /// 解析 TOML 字符串，`include` 的相对路径基于当前目录
+    pub fn from_string(toml: &str) -> Result<Self> {
+        let inner = load_string(toml, Format::Toml)?;
+        Ok(Self { inner })
+    }
+
+    pub fn new(table: Table) -> TomlConfig {
+        TomlConfig { inner: table }
+    }
+}
+
+/// 读取配置文件，合并 `include` 引用的文件后替换 `${...}` 引用
+fn load_path<P: AsRef<Path>>(path: P, format: Format) -> Result<Table> {
+    resolve(include::load_path(path, format)?)
+}
+
+/// 解析字符串，`include` 的相对路径基于当前目录
+fn load_string(text: &str, format: Format) -> Result<Table> {
+    resolve(include::load_includes(
+        format.parse(text)?,
+        Path::new("."),
+        format,
+    )?)
+}
+
+fn resolve(table: Table) -> Result<Table> {
+    interpolate::interpolate(&table, &|name| std::env::var(name).ok())
+}
+
+/// YAML 与 JSON 格式的配置，解析后与 `TomlConfig` 使用相同的键查找规则，值为 null 的键视为不存在
+macro_rules! table_config {
+    ($name: ident, $format: expr) => {
+        pub struct $name {
+            inner: Table,
+        }
+
+        impl $name {
+            /// 读取配置文件，合并 `include` 引用的文件后替换 `${...}` 引用
+            pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+                let inner = load_path(path, $format)?;
+                Ok(Self { inner })
+            }
+
+            /// 解析字符串，`include` 的相对路径基于当前目录
+            pub fn from_string(text: &str) -> Result<Self> {
+                let inner = load_string(text, $format)?;
+                Ok(Self { inner })
+            }
+        }
+
+        impl Config for $name {
+            fn get_value<K: Into<String>, T: TryFrom<Value, Error = Error>>(
+                &self,
+                key: K,
+            ) -> Result<T> {
+                self.inner.get_value(key)
+            }
+
+            fn get_keys<K: Into<String>>(&self, key: K) -> Result<Vec<String>> {
+                self.inner.get_keys(key)
+            }
+        }
+    };
+}
+
+table_config!(YamlConfig, Format::Yaml);
+table_config!(JsonConfig, Format::Json);
+
+/// 根据扩展名选择格式的配置文件
+pub enum FileConfig {
+    Toml(TomlConfig),
+    Yaml(YamlConfig),
+    Json(JsonConfig),
+}
+
+impl FileConfig {
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let config = match Format::from_path(&path)? {
+            Format::Toml => FileConfig::Toml(TomlConfig::from_path(path)?),
+            Format::Yaml => FileConfig::Yaml(YamlConfig::from_path(path)?),
+            Format::Json => FileConfig::Json(JsonConfig::from_path(path)?),
+        };
+        Ok(config)
+    }
+
+    pub fn format(&self) -> Format {
+        match self {
+            FileConfig::Toml(_) => Format::Toml,
+            FileConfig::Yaml(_) => Format::Yaml,
+            FileConfig::Json(_) => Format::Json,
+        }
+    }
+}
+
+impl Config for FileConfig {
+    fn get_value<K: Into<String>, T: TryFrom<Value, Error = Error>>(&self, key: K) -> Result<T> {
+        match self {
+            FileConfig::Toml(config) => config.get_value(key),
+            FileConfig::Yaml(config) => config.get_value(key),
+            FileConfig::Json(config) => config.get_value(key),
+        }
+    }
+
+    fn get_keys<K: Into<String>>(&self, key: K) -> Result<Vec<String>> {
+        match self {
+            FileConfig::Toml(config) => config.get_keys(key),
+            FileConfig::Yaml(config) => config.get_keys(key),
+            FileConfig::Json(config) => config.get_keys(key),
+        }
+    }
+}
+
+impl TomlConfig {
+    pub fn get_values(&self) -> HashMap<String, toml::Value> {
+        let mut values = HashMap::new();
+        for (key, value) in &self.inner {
+            values.insert(key.clone(), value.clone());
+        }
+        values
+    }
+}
+
+impl Config for Table {
+    fn get_value<K: Into<String>, T: TryFrom<Value, Error = Error>>(&self, key: K) -> Result<T> {
+        let key = key.into();
+        let value = key::lookup(self, &key::parse(&key)?)
+            .ok_or_else(|| Error::invalid_index(&format!("can't get config[{}]", key)))?;
+        T::try_from(value.to())
+    }
+
+    fn get_keys<K: Into<String>>(&self, key: K) -> Result<Vec<String>> {
+        let key = key.into();
+        let segments = key::parse(&key)?;
+        let table = if segments.is_empty() {
+            Some(self)
+        } else {
+            key::lookup(self, &segments).and_then(TomlValue::as_table)
+        };
+        let table = table
+            .ok_or_else(|| Error::invalid_index(&format!("can't get config table[{}]", key)))?;
+        Ok(table.keys().cloned().collect())
+    }
+}
+
+impl Config for TomlConfig {
+    fn get_value<K: Into<String>, T: TryFrom<Value, Error = Error>>(&self, key: K) -> Result<T> {
+        self.inner.get_value(key)
+    }
+
+    fn get_keys<K: Into<String>>(&self, key: K) -> Result<Vec<String>> {
+        self.inner.get_keys(key)
+    }
+}
+
+#[test]
+fn test_get_keys() {
+    let config = TomlConfig::from_string(
+        r#"
+        buffer_size = 10
+        [processor.validate]
+        mode = "coerce"
+        [processor.validate.fields]
+        from = "string"
+        "#,
+    )
+    .unwrap();
+    let mut keys = config.get_keys("").unwrap();
+    keys.sort();
+    assert_eq!(vec!["buffer_size", "processor"], keys);
+    assert_eq!(
+        vec!["from"],
+        config.get_keys("processor.validate.fields").unwrap()
+    );
+    assert!(config.get_keys("buffer_size").is_err());
+    assert!(config.get_keys("output").is_err());
+}
+
+#[test]
+fn test_get_value() {
+    let dir = std::env::temp_dir().join("producer_test_get_value");
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(
+        dir.join(CONFIG_FILES[0]),
+        "[riemann]\nhost = \"localhost\"\nport = 5555\n",
+    )
+    .unwrap();
+    let path = ConfigPaths::with(None, None, Some(dir), None)
+        .find()
+        .unwrap();
+    let config = TomlConfig::from_path(path).unwrap();
+    let host = config.get_value::<&str, String>("riemann.host").unwrap();
+    assert_eq!("localhost", host);
+    assert_eq!(5555, config.get_value::<&str, i32>("riemann.port").unwrap());
+}
+
+#[test]
+fn test_formats() {
+    let yaml = YamlConfig::from_string(
+        r#"
+        buffer_size: 10
+        input:
+          rpc_uri: "http://localhost:8545"
+          contracts: ["0x465a4A8DAA955B837957230385AC4A9997aa9d27"]
+          abi: ~
+          ratio: 0.5
+        "#,
+    )
+    .unwrap();
+    let json = JsonConfig::from_string(
+        r#"{
+            "buffer_size": 10,
+            "input": {
+                "rpc_uri": "http://localhost:8545",
+                "contracts": ["0x465a4A8DAA955B837957230385AC4A9997aa9d27"],
+                "abi": null,
+                "ratio": 0.5
+            }
+        }"#,
+    )
+    .unwrap();
+    assert_eq!(10, yaml.get_value::<_, i32>("buffer_size").unwrap());
+    assert_eq!(10, json.get_value::<_, i32>("buffer_size").unwrap());
+    assert_eq!(
+        "http://localhost:8545",
+        yaml.get_value::<_, String>("input.rpc_uri").unwrap()
+    );
+    assert_eq!(
+        yaml.get_value::<_, Vec<String>>("input.contracts").unwrap(),
+        json.get_value::<_, Vec<String>>("input.contracts").unwrap()
+    );
+    assert_eq!(0.5, json.get_value::<_, f64>("input.ratio").unwrap());
+    assert!(yaml
+        .get_value::<_, String>("input.abi")
+        .unwrap_err()
+        .is_invalid_index_err());
+    assert!(json
+        .get_value::<_, String>("input.abi")
+        .unwrap_err()
+        .is_invalid_index_err());
+    assert_eq!(
+        vec!["contracts", "ratio", "rpc_uri"],
+        json.get_keys("input").unwrap()
+    );
+
+    let err = JsonConfig::from_string(r#"{"a": [1, null]}"#)
+        .err()
+        .unwrap();
+    assert_eq!(
+        "config file is not valid JSON - a[1] is null, arrays can't contain null",
+        err.get_msg()
+    );
+    let err = YamlConfig::from_string("a: 18446744073709551615")
+        .err()
+        .unwrap();
+    assert!(err.get_msg().contains("out of range for i64 at a"));
+    assert!(YamlConfig::from_string("")
+        .unwrap()
+        .get_keys("")
+        .unwrap()
+        .is_empty());
+    assert!(YamlConfig::from_string("- 1").is_err());
+}
+
+#[test]
+fn test_file_config() {
+    let dir = std::env::temp_dir().join("producer_test_file_config");
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("input.toml"), "[input]\nmax_thread = 2\n").unwrap();
+    std::fs::write(
+        dir.join("producer.yml"),
+        "include: [input.toml]\ninput:\n  rpc_uri: http://${input.max_thread}\n",
+    )
+    .unwrap();
+
+    let config = FileConfig::from_path(dir.join("producer.yml")).unwrap();
+    assert_eq!(Format::Yaml, config.format());
+    assert_eq!(2, config.get_value::<_, i32>("input.max_thread").unwrap());
+    assert_eq!(
+        "http://2",
+        config.get_value::<_, String>("input.rpc_uri").unwrap()
+    );
+    assert!(FileConfig::from_path(dir.join("producer.ini")).is_err());
+}
+
+#[test]
+fn test_key_path() {
+    let config = YamlConfig::from_string(
+        r#"
+        input:
+          contracts:
+            - address: "0x01"
+            - address: "0x02"
+        outputs:
+          - path: /tmp/a
+            labels:
+              app.kubernetes.io/name: producer
+        "#,
+    )
+    .unwrap();
+    assert_eq!(
+        "0x02",
+        config
+            .get_value::<_, String>("input.contracts[1].address")
+            .unwrap()
+    );
+    assert_eq!(
+        "/tmp/a",
+        config.get_value::<_, String>("outputs.0.path").unwrap()
+    );
+    assert_eq!(
+        "producer",
+        config
+            .get_value::<_, String>(r#"outputs[0].labels."app.kubernetes.io/name""#)
+            .unwrap()
+    );
+    assert!(config
+        .get_value::<_, String>("input.contracts[2].address")
+        .unwrap_err()
+        .is_invalid_index_err());
+    assert!(config.get_value::<_, String>("input.contracts[").is_err());
+
+    let output = config.get_section("outputs[0]").unwrap();
+    assert_eq!("/tmp/a", output.get_value::<_, String>("path").unwrap());
+    assert_eq!(vec!["labels", "path"], output.get_keys("").unwrap());
+    let labels = output.get_section("labels").unwrap();
+    assert_eq!(
+        "producer",
+        labels
+            .get_value::<_, String>("'app.kubernetes.io/name'")
+            .unwrap()
+    );
+    assert!(config.get_section("input.contracts").is_err());
+    assert!(config.get_section("decoder").is_err());
+}
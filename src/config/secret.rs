@@ -0,0 +1,214 @@
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt::{Debug, Display};
+use std::io::{IsTerminal, Write};
+use std::path::Path;
+use std::sync::{Mutex, OnceLock};
+
+use crate::{DataType, Error, Result, ToType, Value};
+
+/// 读取 keystore 密码的环境变量，未设置时在启动时从终端读取
+pub const KEYSTORE_PASSWORD_ENV: &str = "PRODUCER_KEYSTORE_PASSWORD";
+
+/// 敏感的配置值，如带有 API key 的 RPC 地址与私钥，`Display` 与 `Debug` 时不输出内容
+///
+/// 配置中的字符串按前缀读取：
+/// - `env:NAME`：环境变量 `NAME` 的值
+/// - `file:PATH`：文件的内容，去掉末尾的换行
+/// - `keystore:PATH`：解密 keystore JSON 得到的私钥（`0x` 开头的十六进制），
+///   密码来自 `PRODUCER_KEYSTORE_PASSWORD` 环境变量，未设置时从终端询问一次
+/// - 其他：字符串本身
+#[derive(Clone, PartialEq, Eq)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn new<S: Into<String>>(secret: S) -> Self {
+        Secret(secret.into())
+    }
+
+    /// 按前缀读取，`env` 用于读取环境变量
+    pub fn load<E: Fn(&str) -> Option<String>>(value: &str, env: &E) -> Result<Self> {
+        if let Some(name) = value.strip_prefix("env:") {
+            return env(name).map(Secret).ok_or_else(|| {
+                Error::invalid_param(&format!("secret env var {} is not set", name))
+            });
+        }
+        if let Some(path) = value.strip_prefix("file:") {
+            let content = std::fs::read_to_string(path).map_err(|err| {
+                Error::new(
+                    Error::from(err).get_code(),
+                    &format!("can't read secret file {}", path),
+                )
+            })?;
+            return Ok(Secret(content.trim_end_matches(['\r', '\n']).to_owned()));
+        }
+        if let Some(path) = value.strip_prefix("keystore:") {
+            return match env(KEYSTORE_PASSWORD_ENV) {
+                Some(password) => decrypt_keystore(path, &password),
+                None => prompt_keystore(path),
+            };
+        }
+        Ok(Secret(value.to_owned()))
+    }
+
+    /// 读取原始内容，调用方需要避免输出到日志
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+
+    /// 去掉错误信息中的原始内容，如 HTTP 错误中带有的 RPC 地址
+    ///
+    /// 错误链中的信息都可能包含密文，存在密文时把整条链压平成一条信息再替换。
+    pub fn redact(&self, err: Error) -> Error {
+        let msg = err.chain_msg();
+        if self.0.is_empty() || !msg.contains(&self.0) {
+            return err;
+        }
+        Error::new(err.get_code(), &self.redact_str(&msg))
+    }
+
+    /// 去掉文本中的原始内容
+    pub fn redact_str(&self, text: &str) -> String {
+        if self.0.is_empty() {
+            return text.to_owned();
+        }
+        text.replace(&self.0, "[REDACTED]")
+    }
+}
+
+impl Display for Secret {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[REDACTED]")
+    }
+}
+
+impl Debug for Secret {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Secret([REDACTED])")
+    }
+}
+
+impl TryFrom<Value> for Secret {
+    type Error = Error;
+    fn try_from(value: Value) -> Result<Self> {
+        match value {
+            Value::String(value) => Secret::load(&value, &|name| std::env::var(name).ok()),
+            // 错误信息中不能带有原始内容
+            value => Err(Error::invalid_type(&format!(
+                "failed to parse secret for {}",
+                value.get_type()
+            ))),
+        }
+    }
+}
+
+impl ToType for Secret {
+    fn get_type() -> DataType {
+        DataType::String
+    }
+}
+
+/// 解密 keystore，得到 `0x` 开头的十六进制私钥
+pub fn decrypt_keystore<P: AsRef<Path>>(path: P, password: &str) -> Result<Secret> {
+    let path = path.as_ref();
+    let key = eth_keystore::decrypt_key(path, password).map_err(|err| {
+        Error::invalid_auth(&format!(
+            "can't decrypt keystore {} - {}",
+            path.display(),
+            err
+        ))
+    })?;
+    let hex: String = key.iter().map(|b| format!("{:02x}", b)).collect();
+    Ok(Secret(format!("0x{}", hex)))
+}
+
+/// 从终端读取密码解密 keystore，每个文件只询问一次，重新加载配置时使用第一次解密的结果
+///
+/// 标准输入不是终端或在 tokio 运行时中（如热加载）时无法询问，需要设置 `PRODUCER_KEYSTORE_PASSWORD`。
+fn prompt_keystore(path: &str) -> Result<Secret> {
+    static DECRYPTED: OnceLock<Mutex<HashMap<String, Secret>>> = OnceLock::new();
+    let decrypted = DECRYPTED.get_or_init(Default::default);
+    if let Some(secret) = decrypted.lock().unwrap().get(path) {
+        return Ok(secret.clone());
+    }
+    if !std::io::stdin().is_terminal() || tokio::runtime::Handle::try_current().is_ok() {
+        return Err(Error::invalid_auth(&format!(
+            "keystore {} requires {} when stdin is not a terminal or the config is reloaded",
+            path, KEYSTORE_PASSWORD_ENV
+        )));
+    }
+    let secret = decrypt_keystore(path, &read_password(path)?)?;
+    decrypted
+        .lock()
+        .unwrap()
+        .insert(path.to_owned(), secret.clone());
+    Ok(secret)
+}
+
+fn read_password(path: &str) -> Result<String> {
+    eprint!("password for keystore {}: ", path);
+    std::io::stderr().flush()?;
+    let mut password = String::new();
+    std::io::stdin().read_line(&mut password)?;
+    Ok(password.trim_end_matches(['\r', '\n']).to_owned())
+}
+
+#[cfg(test)]
+fn test_env(name: &str) -> Option<String> {
+    match name {
+        "RPC_URI" => Some("https://mainnet.infura.io/v3/key".to_owned()),
+        KEYSTORE_PASSWORD_ENV => Some("bee".to_owned()),
+        _ => None,
+    }
+}
+
+#[test]
+fn test_secret() {
+    let secret = Secret::load("env:RPC_URI", &test_env).unwrap();
+    assert_eq!("https://mainnet.infura.io/v3/key", secret.expose());
+    assert_eq!("[REDACTED]", secret.to_string());
+    assert_eq!("Secret([REDACTED])", format!("{:?}", secret));
+    assert!(Secret::load("env:MISSING", &test_env).is_err());
+    let err = secret.redact(Error::io(
+        "error sending request for url (https://mainnet.infura.io/v3/key)",
+    ));
+    assert_eq!("error sending request for url ([REDACTED])", err.get_msg());
+    let err = secret.redact(
+        Error::io("connect to https://mainnet.infura.io/v3/key failed").context("fetch logs"),
+    );
+    assert_eq!("fetch logs - connect to [REDACTED] failed", err.get_msg());
+
+    let dir = std::env::temp_dir().join("producer_test_secret");
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("key"), "0x01\n").unwrap();
+    let path = format!("file:{}", dir.join("key").display());
+    assert_eq!("0x01", Secret::load(&path, &test_env).unwrap().expose());
+    let err = Secret::load("file:/missing/key", &test_env).unwrap_err();
+    assert!(err.get_msg().contains("/missing/key"));
+
+    assert_eq!("plain", Secret::load("plain", &test_env).unwrap().expose());
+    assert!(Secret::try_from(Value::Integer(1)).is_err());
+}
+
+#[test]
+fn test_keystore() {
+    let dir = std::env::temp_dir().join("producer_test_keystore");
+    std::fs::create_dir_all(&dir).unwrap();
+    let key = [0x11_u8; 32];
+    eth_keystore::encrypt_key(&dir, &mut rand::thread_rng(), key, "bee", Some("key.json")).unwrap();
+
+    let path = format!("keystore:{}", dir.join("key.json").display());
+    let secret = Secret::load(&path, &test_env).unwrap();
+    assert_eq!(format!("0x{}", "11".repeat(32)), secret.expose());
+
+    let err = decrypt_keystore(dir.join("key.json"), "wrong").unwrap_err();
+    assert!(err.is_invalid_auth_err());
+}
+
+#[tokio::test]
+async fn test_keystore_prompt_in_runtime() {
+    // 运行时中（如热加载）没有密码时直接报错，不等待输入
+    let err = Secret::load("keystore:/missing/key.json", &|_| None).unwrap_err();
+    assert!(err.is_invalid_auth_err());
+    assert!(err.get_msg().contains(KEYSTORE_PASSWORD_ENV));
+}
@@ -3,6 +3,7 @@ use std::{fmt::Display, str::FromStr};
 
 /// 数据类型
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum DataType {
     String,
     Integer,
@@ -32,6 +33,8 @@ impl_to_type!(i64, Integer);
 impl_to_type!(i32, Integer);
 impl_to_type!(i16, Integer);
 impl_to_type!(i8, Integer);
+impl_to_type!(u64, Integer);
+impl_to_type!(usize, Integer);
 impl_to_type!(u32, Integer);
 impl_to_type!(u16, Integer);
 impl_to_type!(u8, Integer);
@@ -43,6 +46,7 @@ impl_to_type!(Bytes, Bytes);
 impl_to_type!([u8], Bytes);
 impl_to_type!((), Nil);
 impl_to_type!(Vec<Value>, Nil);
+impl_to_type!(Vec<String>, Array);
 
 impl FromStr for DataType {
     type Err = Error;
@@ -77,7 +81,7 @@ impl FromStr for DataType {
             }
         };
 
-        return Ok(t);
+        Ok(t)
     }
 }
 
@@ -167,3 +171,24 @@ fn test() {
     );
     assert_eq!(DataType::Nil, DataType::from(Value::Nil));
 }
+
+#[cfg(feature = "serde")]
+#[test]
+fn serde_round_trip() {
+    assert_eq!(
+        r#""Integer""#,
+        serde_json::to_string(&DataType::Integer).unwrap()
+    );
+    for t in [
+        DataType::String,
+        DataType::Integer,
+        DataType::Number,
+        DataType::Boolean,
+        DataType::Bytes,
+        DataType::Array,
+        DataType::Nil,
+    ] {
+        let json = serde_json::to_string(&t).unwrap();
+        assert_eq!(t, serde_json::from_str::<DataType>(&json).unwrap());
+    }
+}
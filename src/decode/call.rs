@@ -0,0 +1,139 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use web3::ethabi::{self, Function};
+
+use super::{load_contract, selector};
+use crate::schema::token_value;
+use crate::{event::Event, Error, Result, Value};
+
+/// 按 4 字节选择器匹配 ABI 中的函数，解码交易的调用数据
+///
+/// 解码后的事件包含函数名 `function` 与按参数名命名的参数，参数类型与 `schema::param_type` 一致；
+/// 没有名称的参数使用参数序号。
+pub struct CallDecoder {
+    functions: HashMap<[u8; 4], Function>,
+}
+
+impl CallDecoder {
+    pub fn new(contract: &ethabi::Contract) -> Self {
+        let functions = contract
+            .functions()
+            .map(|function| (selector(&function.name, &function.inputs), function.clone()))
+            .collect();
+        Self { functions }
+    }
+
+    /// 从 ABI JSON 文件中读取所有函数
+    pub fn from_abi_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Ok(Self::new(&load_contract(&std::fs::read(path)?)?))
+    }
+
+    /// 解码调用数据，选择器不在 ABI 中时返回 `None`，参数与函数定义不符时返回 `Error::invalid_data`
+    pub fn decode(&self, input: &[u8]) -> Result<Option<Event>> {
+        let function = match input
+            .get(..4)
+            .and_then(|selector| self.functions.get(selector))
+        {
+            Some(function) => function,
+            None => return Ok(None),
+        };
+        let tokens = function.decode_input(&input[4..]).map_err(|err| {
+            Error::invalid_data(&format!(
+                "failed to decode {} call - {}",
+                function.name, err
+            ))
+        })?;
+
+        let mut event = Event::new();
+        event.insert("function".to_owned(), Value::from(function.name.clone()));
+        for (index, (param, token)) in function.inputs.iter().zip(tokens).enumerate() {
+            let name = if param.name.is_empty() {
+                index.to_string()
+            } else {
+                param.name.clone()
+            };
+            event.insert(name, token_value(&param.kind, token));
+        }
+        Ok(Some(event))
+    }
+}
+
+#[test]
+fn test_call_decoder() {
+    use ethabi::Token;
+    use web3::types::{Address, U256};
+
+    let decoder = CallDecoder::from_abi_path("abi/AuthToken.json").unwrap();
+    let contract =
+        ethabi::Contract::load(std::fs::File::open("abi/AuthToken.json").unwrap()).unwrap();
+    let address = |value: u64| Address::from_low_u64_be(value);
+
+    let input = contract
+        .function("batchMintWithURI")
+        .unwrap()
+        .encode_input(&[
+            Token::Array(vec![Token::Address(address(1)), Token::Address(address(2))]),
+            Token::Array(vec![Token::Uint(7.into()), Token::Uint(U256::MAX)]),
+            Token::Array(vec![
+                Token::String("ipfs://7".to_owned()),
+                Token::String("ipfs://max".to_owned()),
+            ]),
+        ])
+        .unwrap();
+    let event = decoder.decode(&input).unwrap().unwrap();
+    assert_eq!(Value::from("batchMintWithURI"), event["function"]);
+    assert_eq!(
+        Value::Array(vec![
+            Value::from(format!("{:?}", address(1))),
+            Value::from(format!("{:?}", address(2))),
+        ]),
+        event["accounts"]
+    );
+    assert_eq!(
+        Value::Array(vec![Value::from("7"), Value::from(U256::MAX.to_string())]),
+        event["ids"]
+    );
+    assert_eq!(4, event.len());
+
+    // 重载的函数按完整签名区分
+    let functions = contract.functions_by_name("safeTransferFrom").unwrap();
+    let with_data = functions.iter().find(|f| f.inputs.len() == 4).unwrap();
+    let input = with_data
+        .encode_input(&[
+            Token::Address(address(1)),
+            Token::Address(address(2)),
+            Token::Uint(3.into()),
+            Token::Bytes(vec![0xab]),
+        ])
+        .unwrap();
+    let event = decoder.decode(&input).unwrap().unwrap();
+    assert_eq!(Value::from("safeTransferFrom"), event["function"]);
+    assert_eq!(Value::from("3"), event["tokenId"]);
+    assert_eq!(
+        Value::Bytes(crate::value::Bytes(vec![0xab])),
+        event["_data"]
+    );
+
+    let input = contract
+        .function("mintWithURI")
+        .unwrap()
+        .encode_input(&[
+            Token::Address(address(3)),
+            Token::Uint(5.into()),
+            Token::String("ipfs://5".to_owned()),
+        ])
+        .unwrap();
+    assert_eq!(
+        Some(&Value::from("ipfs://5")),
+        decoder.decode(&input).unwrap().unwrap().get("tokenURI")
+    );
+
+    assert_eq!(None, decoder.decode(&[0xde, 0xad, 0xbe, 0xef]).unwrap());
+    assert_eq!(None, decoder.decode(&[]).unwrap());
+    let err = decoder.decode(&input[..40]).unwrap_err();
+    assert_eq!(Error::invalid_data("").get_code(), err.get_code());
+    assert!(err
+        .get_msg()
+        .starts_with("failed to decode mintWithURI call"));
+}
@@ -1,7 +1,63 @@
-use crate::{Result, ToEvent};
+use web3::ethabi::{self, Param};
+use web3::signing::keccak256;
+use web3::types::Log;
 
+use crate::{config::Section, event::Event, Config, Error, Result};
+
+pub use self::call::CallDecoder;
+pub use self::revert::{Revert, RevertDecoder};
+use self::transfer::TransferDecoder;
+
+mod call;
+mod revert;
 mod transfer;
 
-pub trait Decoder {
-    fn decode<T: ToEvent>(bytes: [u8; 0]) -> Result<T>;
+/// 将输入读取到的日志解码为事件
+pub trait Decoder: Send + Sync {
+    /// 解码一条日志，不关心的日志返回 `None`
+    fn decode(&self, log: &Log) -> Result<Option<Event>>;
+}
+
+/// `[decoder]` 的配置，由 `type` 决定解码器类型
+#[derive(Debug, Clone, PartialEq)]
+pub enum DecoderConfig {
+    Transfer,
+}
+
+impl DecoderConfig {
+    pub fn from_section<C: Config>(section: &mut Section<C>) -> Option<Self> {
+        match section.required::<String>("type")?.as_str() {
+            "transfer" => Some(DecoderConfig::Transfer),
+            other => {
+                section.error(
+                    "type",
+                    format!("unknown decoder type {}, expects transfer", other),
+                );
+                None
+            }
+        }
+    }
+
+    pub fn build(&self) -> Result<Box<dyn Decoder>> {
+        match self {
+            DecoderConfig::Transfer => Ok(Box::new(TransferDecoder::new())),
+        }
+    }
+}
+
+/// 函数或自定义错误的选择器，即 `name(type,...)` 的 keccak256 哈希的前 4 字节
+pub(crate) fn selector(name: &str, params: &[Param]) -> [u8; 4] {
+    let kinds: Vec<String> = params.iter().map(|param| param.kind.to_string()).collect();
+    let hash = keccak256(format!("{}({})", name, kinds.join(",")).as_bytes());
+    [hash[0], hash[1], hash[2], hash[3]]
+}
+
+/// 读取 ABI JSON，ethabi 不支持 `type = "error"` 的项，解析前去掉，自定义错误见 `RevertDecoder`
+pub(crate) fn load_contract(json: &[u8]) -> Result<ethabi::Contract> {
+    let mut items: Vec<serde_json::Value> = serde_json::from_slice(json)
+        .map_err(|err| Error::invalid_data(&format!("invalid abi - {}", err)))?;
+    items.retain(|item| item["type"] != "error");
+    let contract = serde_json::to_vec(&items)
+        .map_err(|err| Error::invalid_data(&format!("invalid abi - {}", err)))?;
+    Ok(ethabi::Contract::load(contract.as_slice())?)
 }
@@ -0,0 +1,272 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::path::Path;
+
+use web3::ethabi::{self, Param, ParamType, Token};
+use web3::types::U256;
+
+use super::selector;
+use crate::schema::token_value;
+use crate::{Error, Result, Value};
+
+/// `Error(string)` 的选择器
+const ERROR_SELECTOR: [u8; 4] = [0x08, 0xc3, 0x79, 0xa0];
+/// `Panic(uint256)` 的选择器
+const PANIC_SELECTOR: [u8; 4] = [0x4e, 0x48, 0x7b, 0x71];
+
+/// 合约回滚的原因，作为 `Error::reverted` 的 `source`，通过 `Error::revert` 取出
+#[derive(Debug, Clone, PartialEq)]
+pub enum Revert {
+    /// `require(cond, "reason")` 或 `revert("reason")`，即 `Error(string)`
+    Reason(String),
+    /// 断言失败、算术溢出、数组越界等，即 `Panic(uint256)`
+    Panic(U256),
+    /// ABI 中声明的自定义错误，参数类型与 `schema::param_type` 一致
+    Custom {
+        name: String,
+        args: Vec<(String, Value)>,
+    },
+    /// 无法识别的回滚数据，可以通过 `RevertDecoder::resolve` 按 ABI 重新解码
+    Unknown(Vec<u8>),
+}
+
+impl Display for Revert {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Revert::Reason(reason) => write!(f, "execution reverted: {}", reason),
+            Revert::Panic(code) => write!(
+                f,
+                "execution reverted: panic {:#04x} ({})",
+                code,
+                panic_message(*code)
+            ),
+            Revert::Custom { name, args } => {
+                let args: Vec<String> = args
+                    .iter()
+                    .map(|(name, value)| format!("{}={}", name, value))
+                    .collect();
+                write!(f, "execution reverted: {}({})", name, args.join(", "))
+            }
+            Revert::Unknown(data) => write!(f, "execution reverted with data 0x{}", hex(data)),
+        }
+    }
+}
+
+impl std::error::Error for Revert {}
+
+/// Solidity 编译器定义的 panic 代码
+fn panic_message(code: U256) -> &'static str {
+    if code > U256::from(u8::MAX) {
+        return "unknown panic";
+    }
+    match code.low_u64() {
+        0x00 => "generic compiler panic",
+        0x01 => "assertion failed",
+        0x11 => "arithmetic underflow or overflow",
+        0x12 => "division or modulo by zero",
+        0x21 => "invalid enum value",
+        0x22 => "invalid storage byte array encoding",
+        0x31 => "pop on empty array",
+        0x32 => "array index out of bounds",
+        0x41 => "out of memory",
+        0x51 => "call to zero-initialized function",
+        _ => "unknown panic",
+    }
+}
+
+fn hex(data: &[u8]) -> String {
+    data.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// 解码回滚数据，除 `Error(string)` 与 `Panic(uint256)` 外还识别 ABI 中声明的自定义错误
+#[derive(Debug, Clone, Default)]
+pub struct RevertDecoder {
+    /// 选择器到自定义错误的名称与参数
+    errors: HashMap<[u8; 4], (String, Vec<Param>)>,
+}
+
+impl RevertDecoder {
+    /// 只识别 `Error(string)` 与 `Panic(uint256)`
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 读取 ABI JSON 中 `type = "error"` 的项
+    pub fn from_abi(json: &[u8]) -> Result<Self> {
+        let items: Vec<serde_json::Value> = serde_json::from_slice(json)
+            .map_err(|err| Error::invalid_data(&format!("invalid abi - {}", err)))?;
+        let mut errors = HashMap::new();
+        for item in items.iter().filter(|item| item["type"] == "error") {
+            let name = item["name"].as_str().unwrap_or_default().to_owned();
+            let inputs: Vec<Param> =
+                serde_json::from_value(item["inputs"].clone()).map_err(|err| {
+                    Error::invalid_data(&format!("invalid abi error {} - {}", name, err))
+                })?;
+            errors.insert(selector(&name, &inputs), (name, inputs));
+        }
+        Ok(Self { errors })
+    }
+
+    pub fn from_abi_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::from_abi(&std::fs::read(path)?)
+    }
+
+    /// 解码回滚数据，没有数据时返回 `None`
+    pub fn decode(&self, data: &[u8]) -> Option<Revert> {
+        if data.is_empty() {
+            return None;
+        }
+        let (selector, params) = data.split_at(data.len().min(4));
+        let revert = match selector {
+            s if s == ERROR_SELECTOR => match ethabi::decode(&[ParamType::String], params) {
+                Ok(tokens) => match &tokens[..] {
+                    [Token::String(reason)] => Some(Revert::Reason(reason.clone())),
+                    _ => None,
+                },
+                Err(_) => None,
+            },
+            s if s == PANIC_SELECTOR => match ethabi::decode(&[ParamType::Uint(256)], params) {
+                Ok(tokens) => match &tokens[..] {
+                    [Token::Uint(code)] => Some(Revert::Panic(*code)),
+                    _ => None,
+                },
+                Err(_) => None,
+            },
+            s => self.custom(s, params),
+        };
+        Some(revert.unwrap_or_else(|| Revert::Unknown(data.to_vec())))
+    }
+
+    fn custom(&self, selector: &[u8], params: &[u8]) -> Option<Revert> {
+        let (name, inputs) = self.errors.get(selector)?;
+        let kinds: Vec<ParamType> = inputs.iter().map(|param| param.kind.clone()).collect();
+        let tokens = ethabi::decode(&kinds, params).ok()?;
+        let args = inputs
+            .iter()
+            .zip(tokens)
+            .enumerate()
+            .map(|(index, (param, token))| {
+                let name = if param.name.is_empty() {
+                    index.to_string()
+                } else {
+                    param.name.clone()
+                };
+                (name, token_value(&param.kind, token))
+            })
+            .collect();
+        Some(Revert::Custom {
+            name: name.clone(),
+            args,
+        })
+    }
+
+    /// 用 ABI 中的自定义错误重新解码 `Revert::Unknown`，其他错误原样返回
+    ///
+    /// 需要在添加上下文之前调用，重新解码后的错误不保留原来的上下文。
+    pub fn resolve(&self, err: Error) -> Error {
+        let revert = match err.revert() {
+            Some(Revert::Unknown(data)) => self.decode(data),
+            _ => None,
+        };
+        match revert {
+            Some(revert @ Revert::Custom { .. }) => Error::reverted_with(revert),
+            _ => err,
+        }
+    }
+}
+
+#[cfg(test)]
+const CUSTOM_ERRORS: &str = r#"[
+    {"type": "error", "name": "SoldOut", "inputs": [{"name": "limit", "type": "uint256"}]},
+    {"type": "error", "name": "Unauthorized", "inputs": [{"name": "", "type": "address"}]},
+    {"type": "function", "name": "mint", "inputs": [], "outputs": [], "stateMutability": "nonpayable"}
+]"#;
+
+#[test]
+fn test_revert_decoder() {
+    let decoder = RevertDecoder::from_abi(CUSTOM_ERRORS.as_bytes()).unwrap();
+    let contract = super::load_contract(CUSTOM_ERRORS.as_bytes()).unwrap();
+    assert!(contract.function("mint").is_ok());
+    let encode = |selector: [u8; 4], tokens: &[Token]| {
+        let mut data = selector.to_vec();
+        data.extend(ethabi::encode(tokens));
+        data
+    };
+
+    let revert = decoder
+        .decode(&encode(
+            ERROR_SELECTOR,
+            &[Token::String("sold out".to_owned())],
+        ))
+        .unwrap();
+    assert_eq!(Revert::Reason("sold out".to_owned()), revert);
+    assert_eq!("execution reverted: sold out", revert.to_string());
+
+    let revert = decoder
+        .decode(&encode(PANIC_SELECTOR, &[Token::Uint(0x11.into())]))
+        .unwrap();
+    assert_eq!(Revert::Panic(0x11.into()), revert);
+    assert_eq!(
+        "execution reverted: panic 0x11 (arithmetic underflow or overflow)",
+        revert.to_string()
+    );
+
+    let sold_out = selector(
+        "SoldOut",
+        &[Param {
+            name: "limit".to_owned(),
+            kind: ParamType::Uint(256),
+        }],
+    );
+    let revert = decoder
+        .decode(&encode(sold_out, &[Token::Uint(100.into())]))
+        .unwrap();
+    assert_eq!("execution reverted: SoldOut(limit=100)", revert.to_string());
+    assert_eq!(
+        Revert::Unknown(sold_out.to_vec()),
+        RevertDecoder::new().decode(&sold_out).unwrap()
+    );
+    assert_eq!(None, decoder.decode(&[]));
+    assert_eq!(
+        "execution reverted with data 0xdeadbeef",
+        decoder
+            .decode(&[0xde, 0xad, 0xbe, 0xef])
+            .unwrap()
+            .to_string()
+    );
+}
+
+#[test]
+fn test_resolve() {
+    let decoder = RevertDecoder::from_abi(CUSTOM_ERRORS.as_bytes()).unwrap();
+    let unauthorized = selector(
+        "Unauthorized",
+        &[Param {
+            name: String::new(),
+            kind: ParamType::Address,
+        }],
+    );
+    let mut data = unauthorized.to_vec();
+    data.extend(ethabi::encode(&[Token::Address(
+        web3::types::Address::from_low_u64_be(1),
+    )]));
+
+    let err = Error::reverted_with(RevertDecoder::new().decode(&data).unwrap());
+    assert!(matches!(err.revert(), Some(Revert::Unknown(_))));
+    let err = decoder.resolve(err);
+    assert!(err.is_reverted());
+    assert_eq!(
+        "execution reverted: Unauthorized(0=0x0000000000000000000000000000000000000001)",
+        err.get_msg()
+    );
+    match err.revert() {
+        Some(Revert::Custom { name, args }) => {
+            assert_eq!("Unauthorized", name);
+            assert_eq!("0", args[0].0);
+        }
+        other => panic!("expects custom error, got {:?}", other),
+    }
+
+    let timeout = Error::timeout("read timed out");
+    assert_eq!(timeout, decoder.resolve(timeout.clone()));
+}
@@ -1,11 +1,127 @@
-use crate::{Result, ToEvent};
+use web3::signing::keccak256;
+use web3::types::{Log, H160, H256, U256};
+
+use crate::{event::Event, Error, Result, Value};
 
 use super::Decoder;
 
-pub struct TransferDecoder {}
+/// `Transfer(address,address,uint256)` 的事件签名
+const TRANSFER: &str = "Transfer(address,address,uint256)";
+
+/// 解码 ERC721 `Transfer(from, to, tokenId)` 与 ERC20 `Transfer(from, to, value)` 日志
+pub struct TransferDecoder {
+    signature: H256,
+}
+
+impl TransferDecoder {
+    pub fn new() -> Self {
+        Self {
+            signature: H256::from(keccak256(TRANSFER.as_bytes())),
+        }
+    }
+}
+
+impl Default for TransferDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 impl Decoder for TransferDecoder {
-    fn decode<T: ToEvent>(bytes: [u8; 0]) -> Result<T> {
-        todo!()
+    fn decode(&self, log: &Log) -> Result<Option<Event>> {
+        if log.topics.first() != Some(&self.signature) || log.is_removed() {
+            return Ok(None);
+        }
+
+        // ERC721 的 tokenId 是索引参数，ERC20 的 value 在 data 中
+        let (name, amount) = match log.topics.len() {
+            4 => ("tokenId", U256::from_big_endian(log.topics[3].as_bytes())),
+            3 if log.data.0.len() == 32 => ("value", U256::from_big_endian(&log.data.0)),
+            _ => {
+                return Err(Error::invalid_data(&format!(
+                    "malformed Transfer log in transaction {:?}",
+                    log.transaction_hash.unwrap_or_default()
+                )))
+            }
+        };
+
+        let mut event = Event::new();
+        event.insert("contract".to_owned(), address(log.address));
+        event.insert("from".to_owned(), topic_address(&log.topics[1]));
+        event.insert("to".to_owned(), topic_address(&log.topics[2]));
+        event.insert(name.to_owned(), Value::from(amount.to_string()));
+        event.insert(
+            "block_number".to_owned(),
+            Value::from(log.block_number.map(|number| number.as_u64() as i64)),
+        );
+        event.insert(
+            "transaction_hash".to_owned(),
+            Value::from(log.transaction_hash.map(|hash| format!("{:?}", hash))),
+        );
+        event.insert(
+            "log_index".to_owned(),
+            Value::from(log.log_index.map(|index| index.as_u64() as i64)),
+        );
+        Ok(Some(event))
     }
 }
+
+fn address(address: H160) -> Value {
+    Value::from(format!("{:?}", address))
+}
+
+fn topic_address(topic: &H256) -> Value {
+    address(H160::from_slice(&topic.as_bytes()[12..]))
+}
+
+#[cfg(test)]
+fn transfer_log(topics: Vec<H256>, data: Vec<u8>) -> Log {
+    Log {
+        address: H160::from_low_u64_be(0xaa),
+        topics,
+        data: web3::types::Bytes(data),
+        block_hash: None,
+        block_number: Some(10.into()),
+        transaction_hash: Some(H256::from_low_u64_be(1)),
+        transaction_index: None,
+        log_index: Some(2.into()),
+        transaction_log_index: None,
+        log_type: None,
+        removed: None,
+    }
+}
+
+#[test]
+fn test_transfer_decoder() {
+    let decoder = TransferDecoder::new();
+    let signature = H256::from(keccak256(TRANSFER.as_bytes()));
+    let from = H256::from(H160::from_low_u64_be(1));
+    let to = H256::from(H160::from_low_u64_be(2));
+
+    let log = transfer_log(vec![signature, from, to, H256::from_low_u64_be(7)], vec![]);
+    let event = decoder.decode(&log).unwrap().unwrap();
+    assert_eq!(
+        Some(&Value::from("0x0000000000000000000000000000000000000001")),
+        event.get("from")
+    );
+    assert_eq!(
+        Some(&Value::from("0x0000000000000000000000000000000000000002")),
+        event.get("to")
+    );
+    assert_eq!(Some(&Value::from("7")), event.get("tokenId"));
+    assert_eq!(Some(&Value::Integer(10)), event.get("block_number"));
+    assert_eq!(Some(&Value::Integer(2)), event.get("log_index"));
+
+    let value = web3::ethabi::encode(&[web3::ethabi::Token::Uint(U256::from(1000))]);
+    let event = decoder
+        .decode(&transfer_log(vec![signature, from, to], value))
+        .unwrap()
+        .unwrap();
+    assert_eq!(Some(&Value::from("1000")), event.get("value"));
+
+    let other = transfer_log(vec![H256::zero()], vec![]);
+    assert!(decoder.decode(&other).unwrap().is_none());
+    assert!(decoder
+        .decode(&transfer_log(vec![signature, from], vec![]))
+        .is_err());
+}
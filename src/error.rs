@@ -1,4 +1,8 @@
+use std::error::Error as StdError;
 use std::fmt::Display;
+use std::sync::Arc;
+
+use crate::decode::{Revert, RevertDecoder};
 
 #[macro_export]
 macro_rules! code {
@@ -14,6 +18,7 @@ macro_rules! from_code {
             Self {
                 code: $variant,
                 msg: err.to_string(),
+                source: None,
             }
         }
     };
@@ -87,12 +92,24 @@ const CHANNEL_CLOSE: i32 = code!(CHANNEL, 0x03);
 const OS_SYSTEM: i32 = code!(OS, 0x01);
 
 const WEB_CONTRACT: i32 = code!(WEB3, 0x01);
+const WEB3_RATE_LIMITED: i32 = code!(WEB3, 0x02);
+const WEB3_RANGE_TOO_LARGE: i32 = code!(WEB3, 0x03);
+const WEB3_UNKNOWN_BLOCK: i32 = code!(WEB3, 0x04);
+const WEB3_REVERTED: i32 = code!(WEB3, 0x05);
+const WEB3_INVALID_RESPONSE: i32 = code!(WEB3, 0x06);
+const WEB3_TRANSPORT: i32 = code!(WEB3, 0x07);
+const WEB3_INVALID_REQUEST: i32 = code!(WEB3, 0x08);
 
 /// 错误信息(错误码和错误信息组成)
-#[derive(Debug, Clone, Eq, PartialEq)]
+///
+/// `source` 保存原始错误或通过 `context` 包装前的错误，比较与序列化时忽略。
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Error {
     pub(crate) code: i32,
     pub(crate) msg: String,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub(crate) source: Option<Arc<dyn StdError + Send + Sync>>,
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
@@ -102,25 +119,83 @@ impl Error {
         Self {
             code,
             msg: msg.to_string(),
+            source: None,
+        }
+    }
+
+    /// 保留原始错误作为 `source`
+    pub fn with_source<E: StdError + Send + Sync + 'static>(
+        code: i32,
+        msg: &str,
+        source: E,
+    ) -> Error {
+        Self {
+            code,
+            msg: msg.to_string(),
+            source: Some(Arc::new(source)),
+        }
+    }
+
+    /// 用上下文包装错误，错误码不变，原来的错误作为 `source`
+    pub fn context<C: Display>(self, context: C) -> Error {
+        Self {
+            code: self.code,
+            msg: context.to_string(),
+            source: Some(Arc::new(self)),
         }
     }
 
     pub fn get_code(&self) -> i32 {
-        *(&self.code)
+        self.code
     }
 
+    /// 最外层的错误信息，完整的错误链见 `chain_msg`
     pub fn get_msg(&self) -> &str {
         &self.msg
     }
 
+    /// 从外到内拼接错误链中的信息，跳过与上一层相同的信息
+    pub fn chain_msg(&self) -> String {
+        let mut msgs: Vec<String> = vec![self.msg.clone()];
+        let mut source = StdError::source(self);
+        while let Some(err) = source {
+            let msg = match err.downcast_ref::<Error>() {
+                Some(err) => err.msg.clone(),
+                None => err.to_string(),
+            };
+            if msgs.last() != Some(&msg) {
+                msgs.push(msg);
+            }
+            source = err.source();
+        }
+        msgs.join(" - ")
+    }
+
+    /// 合约回滚，`revert` 作为 `source` 保留，信息为可读的回滚原因
+    pub fn reverted_with(revert: Revert) -> Error {
+        Error::with_source(WEB3_REVERTED, &revert.to_string(), revert)
+    }
+
+    /// 错误链中的回滚原因
+    pub fn revert(&self) -> Option<&Revert> {
+        let mut source = StdError::source(self);
+        while let Some(err) = source {
+            if let Some(revert) = err.downcast_ref::<Revert>() {
+                return Some(revert);
+            }
+            source = err.source();
+        }
+        None
+    }
+
+    /// 拆分为 (基础错误码, 序号)
     pub fn decode(&self) -> (i32, i32) {
-        let base = self.code >> 8;
-        let code = self.code & 0xFF;
-        return (base, code);
+        (self.get_base_code(), self.code >> 8)
     }
 
+    /// 基础错误码在低 8 位，见 `code!`；高位是序号，不能用 `>> 8` 取基础错误码
     pub fn get_base_code(&self) -> i32 {
-        return self.code >> 8;
+        self.code & 0xFF
     }
 
     from_code!(internal, INTERNAL, &str);
@@ -160,6 +235,13 @@ impl Error {
     from_code!(channel_close, CHANNEL_CLOSE, &str);
     from_code!(sql, SQL, &str);
     from_code!(connection_num_limit, SQL_CONNECTION_NUM_LIMIT, &str);
+    from_code!(rate_limited, WEB3_RATE_LIMITED, &str);
+    from_code!(range_too_large, WEB3_RANGE_TOO_LARGE, &str);
+    from_code!(unknown_block, WEB3_UNKNOWN_BLOCK, &str);
+    from_code!(reverted, WEB3_REVERTED, &str);
+    from_code!(invalid_response, WEB3_INVALID_RESPONSE, &str);
+    from_code!(transport, WEB3_TRANSPORT, &str);
+    from_code!(invalid_request, WEB3_INVALID_REQUEST, &str);
 
     is_base_code!(is_sql_err, SQL);
     is_base_code!(is_io_err, IO);
@@ -169,6 +251,7 @@ impl Error {
 
     is_code!(is_invalid_database_err, INVALID_DATABASE);
     is_code!(is_invalid_auth_err, INVALID_AUTH);
+    is_code!(is_invalid_index_err, INVALID_INDEX);
     is_code!(is_timeout_err, IO_TIMED_OUT);
     is_code!(is_addr_inuse, IO_ADDR_INUSE, IO_ADDR_NOT_AVAILABLE);
     is_code!(is_not_connected_host, IO_NOT_CONNECTED_HOST);
@@ -191,19 +274,55 @@ impl Error {
         CHANNEL
     );
     is_code!(is_connection_num_limit, SQL_CONNECTION_NUM_LIMIT);
+    is_base_code!(is_web3_err, WEB3);
+    is_code!(is_rate_limited, WEB3_RATE_LIMITED);
+    is_code!(is_range_too_large, WEB3_RANGE_TOO_LARGE);
+    is_code!(is_unknown_block, WEB3_UNKNOWN_BLOCK);
+    is_code!(is_reverted, WEB3_REVERTED);
+    is_code!(is_invalid_response, WEB3_INVALID_RESPONSE);
+    is_code!(is_transport_err, WEB3_TRANSPORT);
+    is_code!(is_invalid_request, WEB3_INVALID_REQUEST);
+
+    // 可重试的错误：连接中断、超时、连接数限制、限流、节点尚未同步到的区块以及
+    // 其他 RPC 节点错误；参数、数据、权限、无效请求、合约回滚与查询范围过大等错误重试也不会成功
+    is_code!(
+        is_retryable,
+        IO_CONNECTION_ABORTED,
+        IO_CONNECTION_REFUSED,
+        IO_CONNECTION_RESET,
+        IO_NOT_CONNECTED,
+        IO_NOT_CONNECTED_HOST,
+        IO_BROKEN_PIPE,
+        IO_UNEXPECTED_EOF,
+        IO_INTERRUPTED,
+        IO_WOULD_BLOCK,
+        IO_TIMED_OUT,
+        SQL_CONNECTION_NUM_LIMIT,
+        WEB3,
+        WEB3_RATE_LIMITED,
+        WEB3_UNKNOWN_BLOCK,
+        WEB3_TRANSPORT
+    );
 }
 
 macro_rules! from_error {
     ($variant: expr, $T:ty) => {
         impl From<$T> for Error {
             fn from(err: $T) -> Self {
-                Error::new($variant, &err.to_string())
+                Error::with_source($variant, &err.to_string(), err)
             }
         }
     };
 }
 
-from_error!(CHANNEL_RECV, tokio::sync::mpsc::error::RecvError);
+from_error!(CHANNEL_RECV, tokio::sync::oneshot::error::RecvError);
+// 保留已有的公开转换，tokio 1.x 的 `mpsc::Receiver::recv` 已不再返回该错误
+#[allow(deprecated)]
+impl From<tokio::sync::mpsc::error::RecvError> for Error {
+    fn from(err: tokio::sync::mpsc::error::RecvError) -> Self {
+        Error::with_source(CHANNEL_RECV, &err.to_string(), err)
+    }
+}
 impl<T> From<tokio::sync::mpsc::error::SendError<T>> for Error {
     fn from(err: tokio::sync::mpsc::error::SendError<T>) -> Self {
         Error::new(CHANNEL_SEND, &err.to_string())
@@ -218,13 +337,21 @@ from_error!(INVALID_TYPE, std::num::ParseFloatError);
 from_error!(IO_TIMED_OUT, tokio::time::error::Elapsed);
 from_error!(OS_SYSTEM, std::time::SystemTimeError);
 
-from_error!(WEB3, web3::Error);
-from_error!(WEB_CONTRACT, web3::contract::Error);
+/// 合约调用中的 RPC 错误按 `web3::Error` 分类，回滚时可以取出 `Revert`
+impl From<web3::contract::Error> for Error {
+    fn from(err: web3::contract::Error) -> Self {
+        match err {
+            web3::contract::Error::Api(err) => Error::from(err),
+            err => Error::with_source(WEB_CONTRACT, &err.to_string(), err),
+        }
+    }
+}
+from_error!(WEB_CONTRACT, web3::ethabi::Error);
 
 impl From<std::io::Error> for Error {
     fn from(err: std::io::Error) -> Self {
         let msg = err.to_string();
-        match err.kind() {
+        let error = match err.kind() {
             std::io::ErrorKind::NotFound => Error::not_found(&msg),
             std::io::ErrorKind::PermissionDenied => Error::permission_denied(&msg),
             std::io::ErrorKind::ConnectionRefused => Error::connection_refused(&msg),
@@ -243,26 +370,279 @@ impl From<std::io::Error> for Error {
             std::io::ErrorKind::Interrupted => Error::interrupted(&msg),
             std::io::ErrorKind::UnexpectedEof => Error::unexpected_eof(&msg),
             _ => Error::io(&msg),
-        }
+        };
+        Error::with_source(error.code, &msg, err)
     }
 }
 
-unsafe impl Send for Error {}
+/// `eth_getLogs` 查询范围过大时各节点返回的信息
+const RANGE_TOO_LARGE_MESSAGES: &[&str] = &[
+    "query returned more than",
+    "block range",
+    "range too large",
+    "range is too large",
+    "too many results",
+    "response size exceeded",
+    "exceeds limit",
+];
+
+const UNKNOWN_BLOCK_MESSAGES: &[&str] = &["unknown block", "header not found", "block not found"];
+
+impl From<web3::Error> for Error {
+    fn from(err: web3::Error) -> Self {
+        let (code, msg) = match &err {
+            web3::Error::Rpc(rpc) => {
+                let code = rpc.code.code();
+                let message = rpc.message.to_lowercase();
+                if code == 3 || message.starts_with("execution reverted") {
+                    let revert = rpc
+                        .data
+                        .clone()
+                        .and_then(|data| serde_json::from_value::<web3::types::Bytes>(data).ok())
+                        .and_then(|data| RevertDecoder::new().decode(&data.0));
+                    match revert {
+                        Some(revert) => return Error::reverted_with(revert),
+                        None => (WEB3_REVERTED, rpc.message.clone()),
+                    }
+                } else {
+                    let code = if contains_any(&message, RANGE_TOO_LARGE_MESSAGES) {
+                        WEB3_RANGE_TOO_LARGE
+                    } else if contains_any(&message, UNKNOWN_BLOCK_MESSAGES) {
+                        WEB3_UNKNOWN_BLOCK
+                    } else if code == -32005 || message.contains("rate limit") {
+                        WEB3_RATE_LIMITED
+                    } else if [-32700, -32600, -32601, -32602].contains(&code) {
+                        WEB3_INVALID_REQUEST
+                    } else {
+                        WEB3
+                    };
+                    (code, format!("{} (code {})", rpc.message, rpc.code.code()))
+                }
+            }
+            web3::Error::Transport(msg) => {
+                let code = if msg.contains("not success: 429") {
+                    WEB3_RATE_LIMITED
+                } else if msg.contains("timed out") || msg.contains("not success: 504") {
+                    IO_TIMED_OUT
+                } else if msg.starts_with("failed to deserialize response") {
+                    WEB3_INVALID_RESPONSE
+                } else {
+                    WEB3_TRANSPORT
+                };
+                (code, err.to_string())
+            }
+            web3::Error::Unreachable => (WEB3_TRANSPORT, err.to_string()),
+            web3::Error::InvalidResponse(_) | web3::Error::Decoder(_) => {
+                (WEB3_INVALID_RESPONSE, err.to_string())
+            }
+            web3::Error::Io(io) => (
+                Error::from(std::io::Error::from(io.kind())).code,
+                err.to_string(),
+            ),
+            _ => (WEB3, err.to_string()),
+        };
+        Error::with_source(code, &msg, err)
+    }
+}
 
-unsafe impl Sync for Error {}
+fn contains_any(message: &str, patterns: &[&str]) -> bool {
+    patterns.iter().any(|pattern| message.contains(pattern))
+}
+
+impl PartialEq for Error {
+    fn eq(&self, other: &Self) -> bool {
+        self.code == other.code && self.msg == other.msg
+    }
+}
+
+impl Eq for Error {}
 
 impl Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{} : {}", self.code, self.msg)
+        write!(f, "{} : {}", self.code, self.chain_msg())
     }
 }
 
-impl std::error::Error for Error {
-    fn description(&self) -> &str {
-        &self.msg
+impl StdError for Error {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        self.source
+            .as_deref()
+            .map(|err| err as &(dyn StdError + 'static))
+    }
+}
+
+/// 为 `Result` 添加上下文，如 `.context("while fetching logs for block 123")`
+pub trait Context<T> {
+    fn context<C: Display>(self, context: C) -> Result<T>;
+
+    /// 只在出错时生成上下文
+    fn with_context<C: Display, F: FnOnce() -> C>(self, context: F) -> Result<T>;
+}
+
+impl<T, E: Into<Error>> Context<T> for std::result::Result<T, E> {
+    fn context<C: Display>(self, context: C) -> Result<T> {
+        self.map_err(|err| err.into().context(context))
+    }
+
+    fn with_context<C: Display, F: FnOnce() -> C>(self, context: F) -> Result<T> {
+        self.map_err(|err| err.into().context(context()))
+    }
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn serde_round_trip() {
+    let err = Error::invalid_type("failed to parse u8 for Number(10.02)");
+    let json = serde_json::to_string(&err).unwrap();
+    assert_eq!(
+        format!(
+            r#"{{"code":{},"msg":"failed to parse u8 for Number(10.02)"}}"#,
+            INVALID_TYPE
+        ),
+        json
+    );
+    let err2: Error = serde_json::from_str(&json).unwrap();
+    assert_eq!(err, err2);
+}
+
+#[test]
+fn test_base_code() {
+    let err = Error::invalid_param("bad");
+    assert_eq!(INVALID, err.get_base_code());
+    assert_eq!((INVALID, 0x05), err.decode());
+    assert!(err.is_invalid_err());
+    assert!(!err.is_io_err());
+    assert!(Error::timeout("").is_io_err());
+    assert!(Error::from(std::io::Error::from(std::io::ErrorKind::NotFound)).is_io_err());
+    assert!(Error::sql("").is_sql_err());
+
+    // `>> 8` 取到的是序号：IO_PERMISSION_DENIED 的序号与 INVALID 的基础码相同，曾被误判为参数错误
+    let denied = Error::permission_denied("");
+    assert_eq!(INVALID, IO_PERMISSION_DENIED >> 8);
+    assert_eq!(IO, denied.get_base_code());
+    assert!(!denied.is_invalid_err());
+    for (code, base) in [
+        (INVALID_PARAM, INVALID),
+        (IO_NOTFOUND, IO),
+        (SQL, SQL),
+        (OS_SYSTEM, OS),
+    ] {
+        assert_eq!(base, Error::new(code, "").get_base_code());
     }
 }
 
+#[cfg(test)]
+fn rpc_error(code: i64, message: &str, data: Option<&str>) -> Error {
+    let rpc = serde_json::json!({ "code": code, "message": message, "data": data });
+    Error::from(web3::Error::Rpc(serde_json::from_value(rpc).unwrap()))
+}
+
+#[test]
+fn test_web3_error() {
+    let err = rpc_error(-32005, "query returned more than 10000 results", None);
+    assert!(err.is_range_too_large());
+    assert_eq!(
+        "query returned more than 10000 results (code -32005)",
+        err.get_msg()
+    );
+    assert!(rpc_error(-32005, "project ID request rate exceeded", None).is_rate_limited());
+    assert!(rpc_error(-32000, "exceed maximum block range: 5000", None).is_range_too_large());
+    assert!(rpc_error(-32000, "header not found", None).is_unknown_block());
+    assert!(rpc_error(-32000, "Unknown block number", None).is_unknown_block());
+    assert_eq!(WEB3, rpc_error(-32603, "internal error", None).get_code());
+    let err = rpc_error(-32601, "the method eth_foo does not exist", None);
+    assert!(err.is_invalid_request());
+    assert!(!err.is_retryable());
+
+    // Error("sold out")
+    let data = "0x08c379a0\
+                0000000000000000000000000000000000000000000000000000000000000020\
+                0000000000000000000000000000000000000000000000000000000000000008\
+                736f6c64206f7574000000000000000000000000000000000000000000000000";
+    let err = rpc_error(3, "execution reverted: sold out", Some(data));
+    assert!(err.is_reverted());
+    assert_eq!("execution reverted: sold out", err.get_msg());
+    assert_eq!(
+        Some(&crate::Revert::Reason("sold out".to_owned())),
+        err.context("while minting").revert()
+    );
+    let err = rpc_error(-32000, "execution reverted", None);
+    assert!(err.is_reverted());
+    assert!(!err.is_retryable());
+
+    let err = Error::from(web3::Error::Transport(
+        "response status code is not success: 429 Too Many Requests".to_owned(),
+    ));
+    assert!(err.is_rate_limited());
+    assert!(err.is_retryable());
+    let err = Error::from(web3::Error::Transport(
+        "failed to deserialize response: expected value".to_owned(),
+    ));
+    assert!(err.is_invalid_response());
+    let err = Error::from(web3::Error::Transport(
+        "failed to send request: connection refused".to_owned(),
+    ));
+    assert!(err.is_transport_err());
+    assert!(err.is_web3_err());
+    let err = Error::from(web3::Error::Transport(
+        "response status code is not success: 504 Gateway Timeout".to_owned(),
+    ));
+    assert!(err.is_timeout_err());
+    assert!(Error::from(web3::Error::InvalidResponse("bad".to_owned())).is_invalid_response());
+    let err = Error::from(web3::Error::Io(std::io::ErrorKind::TimedOut.into()));
+    assert!(err.is_timeout_err());
+    assert!(err
+        .source()
+        .unwrap()
+        .downcast_ref::<web3::Error>()
+        .is_some());
+}
+
+#[test]
+fn test_retryable() {
+    assert!(Error::timeout("").is_retryable());
+    assert!(Error::connection_refused("").is_retryable());
+    assert!(Error::connection_num_limit("").is_retryable());
+    assert!(Error::from(web3::Error::Unreachable).is_retryable());
+    assert!(Error::timeout("")
+        .context("while fetching logs")
+        .is_retryable());
+    assert!(!Error::invalid_param("").is_retryable());
+    assert!(!Error::permission_denied("").is_retryable());
+    assert!(!Error::from(web3::ethabi::Error::InvalidData).is_retryable());
+    assert!(!Error::range_too_large("").is_retryable());
+}
+
+#[test]
+fn test_context() {
+    let io = std::io::Error::new(std::io::ErrorKind::TimedOut, "read timed out");
+    let result: std::result::Result<(), std::io::Error> = Err(io);
+    let err = result
+        .context("while fetching logs for block 123")
+        .with_context(|| format!("input {}", "web3_event"))
+        .unwrap_err();
+
+    assert_eq!(IO_TIMED_OUT, err.get_code());
+    assert!(err.is_timeout_err());
+    assert!(err.is_io_err());
+    assert_eq!("input web3_event", err.get_msg());
+    assert_eq!(
+        "input web3_event - while fetching logs for block 123 - read timed out",
+        err.chain_msg()
+    );
+    assert_eq!(
+        format!("{} : {}", IO_TIMED_OUT, err.chain_msg()),
+        err.to_string()
+    );
+
+    let source = err.source().unwrap();
+    let inner = source.downcast_ref::<Error>().unwrap();
+    assert_eq!("while fetching logs for block 123", inner.get_msg());
+    let io = inner.source().unwrap().source().unwrap();
+    assert!(io.downcast_ref::<std::io::Error>().is_some());
+    assert!(io.source().is_none());
+}
+
 #[test]
 fn test() {
     let err = Error::new(6150, "");
@@ -309,3 +689,9 @@ fn test() {
     println!("WEB3|{}", WEB3);
     println!("WEB_CONTRACT|{}", WEB_CONTRACT);
 }
+
+#[test]
+fn test_send_sync() {
+    fn send_sync<T: Send + Sync>() {}
+    send_sync::<Error>();
+}
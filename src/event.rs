@@ -1,9 +1,120 @@
-use std::iter::Map;
+use std::collections::BTreeMap;
 
-use crate::Value;
+use crate::{Result, Value};
 
-pub type Event = Map<String, Value>;
+/// 事件，字段名到值的有序映射
+pub type Event = BTreeMap<String, Value>;
 
-pub trait ToEvent: Send{
+/// 转为事件，可通过 `#[derive(ToEvent)]` 生成
+pub trait ToEvent: Send {
     fn to(&self) -> Event;
-}
\ No newline at end of file
+}
+
+/// 从事件构造，可通过 `#[derive(FromEvent)]` 生成
+pub trait FromEvent: Sized {
+    fn from_event(event: &Event) -> Result<Self>;
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn serde_round_trip() {
+    let mut event = Event::new();
+    event.insert("from".to_owned(), Value::from("0x00"));
+    event.insert("token_id".to_owned(), Value::from(10));
+    event.insert("removed".to_owned(), Value::from(false));
+
+    let json = serde_json::to_string(&event).unwrap();
+    assert_eq!(
+        r#"{"from":{"String":"0x00"},"removed":{"Boolean":false},"token_id":{"Integer":10}}"#,
+        json
+    );
+    assert_eq!(event, serde_json::from_str::<Event>(&json).unwrap());
+}
+
+#[cfg(test)]
+mod derive {
+    use crate::{Event, FromEvent, ToEvent, Value};
+
+    #[derive(Debug, Clone, PartialEq, Default, ToEvent, FromEvent)]
+    struct Block {
+        #[event(rename = "block_number")]
+        number: i64,
+        hash: String,
+    }
+
+    #[derive(Debug, Clone, PartialEq, ToEvent, FromEvent)]
+    struct Transfer {
+        from: String,
+        #[event(rename = "tokenId")]
+        token_id: i64,
+        memo: Option<String>,
+        #[event(skip)]
+        cached: bool,
+        #[event(flatten)]
+        block: Block,
+    }
+
+    fn transfer() -> Transfer {
+        Transfer {
+            from: "0x00".to_owned(),
+            token_id: 10,
+            memo: None,
+            cached: true,
+            block: Block {
+                number: 100,
+                hash: "0x01".to_owned(),
+            },
+        }
+    }
+
+    #[test]
+    fn to_event() {
+        let event = transfer().to();
+        let mut expect = Event::new();
+        expect.insert("from".to_owned(), Value::from("0x00"));
+        expect.insert("tokenId".to_owned(), Value::from(10));
+        expect.insert("memo".to_owned(), Value::Nil);
+        expect.insert("block_number".to_owned(), Value::from(100));
+        expect.insert("hash".to_owned(), Value::from("0x01"));
+        assert_eq!(expect, event);
+    }
+
+    #[test]
+    fn from_event() {
+        let mut event = transfer().to();
+        let mut expect = transfer();
+        expect.cached = false;
+        assert_eq!(expect, Transfer::from_event(&event).unwrap());
+
+        event.insert("memo".to_owned(), Value::from("gift"));
+        assert_eq!(
+            Some("gift".to_owned()),
+            Transfer::from_event(&event).unwrap().memo
+        );
+
+        event.insert("tokenId".to_owned(), Value::from("10"));
+        let err = Transfer::from_event(&event).unwrap_err();
+        assert!(err
+            .get_msg()
+            .starts_with("failed to parse event field tokenId"));
+
+        event.remove("block_number");
+        assert!(Block::from_event(&event).is_err());
+    }
+
+    #[test]
+    fn from_event_missing() {
+        let mut event = transfer().to();
+        event.remove("memo");
+        assert_eq!(None, Transfer::from_event(&event).unwrap().memo);
+
+        event.remove("from");
+        let err = Transfer::from_event(&event).unwrap_err();
+        assert_eq!(crate::Error::invalid_data("").get_code(), err.get_code());
+        assert_eq!("missing field from", err.get_msg());
+
+        event.insert("from".to_owned(), Value::Nil);
+        let err = Transfer::from_event(&event).unwrap_err();
+        assert_eq!("missing field from", err.get_msg());
+    }
+}
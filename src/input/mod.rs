@@ -1,14 +1,198 @@
 pub mod web3_event;
 pub mod web3_rpc;
 
-use tokio::sync::mpsc::Sender;
-use crate::{decode::Decoder, process::Processor, Config, Result};
-
-pub trait Input {
-    fn start<C: Config, P: Processor, D: Decoder>(
-        config: &C,
-        processor: P,
-        decoder: D,
-        sender: Sender<D>,
-    ) -> Result<()>;
+use std::convert::TryFrom;
+use std::str::FromStr;
+
+use async_trait::async_trait;
+use web3::types::{Address, Log};
+
+use crate::config::{Secret, Section};
+use crate::{
+    event::Event, Config, Context, Error, RateLimitConfig, Result, RetryPolicy, RpcPool, Value,
+};
+
+use self::web3_event::{Web3EventInput, Web3EventInputConfig};
+use self::web3_rpc::{Web3RpcInput, Web3RpcInputConfig};
+
+/// 输入读取到的原始数据，日志需要经过解码器转为事件
+#[derive(Debug, Clone)]
+pub enum Record {
+    Log(Box<Log>),
+    Event(Event),
+}
+
+/// 输入读取到的一批数据
+#[derive(Debug, Clone)]
+pub struct Batch {
+    pub records: Vec<Record>,
+    /// 这批数据发送完成后的检查点，即下一个需要读取的区块
+    pub checkpoint: u64,
+}
+
+/// 数据输入，由管道循环调用 `next` 读取数据
+#[async_trait]
+pub trait Input: Send {
+    /// 从检查点开始读取，不调用时使用配置中的起始位置
+    fn seek(&mut self, checkpoint: u64);
+
+    /// 下一个需要读取的位置
+    fn position(&self) -> u64;
+
+    /// 读取下一批数据，没有新数据时等待后返回空的批次
+    async fn next(&mut self) -> Result<Batch>;
+
+    /// 最后一个需要读取的区块，读取完成后管道停止输入；`None` 表示持续读取
+    fn end(&self) -> Option<u64> {
+        None
+    }
+
+    /// `next` 返回错误后重新读取前等待的时间（毫秒），RPC 调用本身的重试见 `RetryPolicy`
+    fn retry_interval(&self) -> u64 {
+        1000
+    }
+}
+
+/// `[input]` 的配置，由 `type` 决定输入类型
+#[derive(Debug, Clone, PartialEq)]
+pub enum InputConfig {
+    Web3Event(Web3EventInputConfig),
+    Web3Rpc(Web3RpcInputConfig),
+}
+
+impl InputConfig {
+    pub fn from_section<C: Config>(section: &mut Section<C>) -> Option<Self> {
+        match section.required::<String>("type")?.as_str() {
+            "web3_event" => Web3EventInputConfig::from_section(section).map(InputConfig::Web3Event),
+            "web3_rpc" => Web3RpcInputConfig::from_section(section).map(InputConfig::Web3Rpc),
+            other => {
+                section.error(
+                    "type",
+                    format!(
+                        "unknown input type {}, expects web3_event or web3_rpc",
+                        other
+                    ),
+                );
+                None
+            }
+        }
+    }
+
+    pub fn build(&self) -> Result<Box<dyn Input>> {
+        match self {
+            InputConfig::Web3Event(config) => Ok(Box::new(Web3EventInput::new(config.clone())?)),
+            InputConfig::Web3Rpc(config) => Ok(Box::new(Web3RpcInput::new(config.clone())?)),
+        }
+    }
+
+    /// 检查点文件
+    pub fn checkpoint(&self) -> Option<&str> {
+        match self {
+            InputConfig::Web3Event(config) => config.checkpoint.as_deref(),
+            InputConfig::Web3Rpc(config) => config.checkpoint.as_deref(),
+        }
+    }
+
+    /// 只读取 `from..=to` 的区块，不使用检查点文件
+    ///
+    /// 用于回填历史区块，避免覆盖持续运行的管道的检查点。
+    pub fn set_range(&mut self, from: u64, to: u64) -> Result<()> {
+        if from > to {
+            return Err(Error::invalid_param(&format!(
+                "from block {} is greater than to block {}",
+                from, to
+            )));
+        }
+        match self {
+            InputConfig::Web3Event(config) => {
+                config.from_block = from;
+                config.to_block = Some(to);
+                config.checkpoint = None;
+            }
+            InputConfig::Web3Rpc(config) => {
+                config.from_block = from;
+                config.to_block = Some(to);
+                config.checkpoint = None;
+            }
+        }
+        Ok(())
+    }
+
+    /// 输入使用的线程数
+    pub fn max_thread(&self) -> usize {
+        match self {
+            InputConfig::Web3Event(config) => config.max_thread,
+            InputConfig::Web3Rpc(config) => config.max_thread,
+        }
+    }
+}
+
+/// 第一次读取前检查所有节点的链 ID 是否一致
+async fn check_chain_id(pool: &RpcPool, retry: &RetryPolicy) -> Result<()> {
+    let chain_id = retry
+        .retry(|| pool.check_chain_id())
+        .await
+        .context("while checking chain id")?;
+    log::info!(
+        "connected to chain {} through {} rpc endpoint(s)",
+        chain_id,
+        pool.len()
+    );
+    Ok(())
+}
+
+/// 读取 `rpc_uri`，可以是一个地址或地址数组
+pub(crate) fn rpc_uris<C: Config>(section: &mut Section<C>) -> Option<Vec<Secret>> {
+    match section.value("rpc_uri") {
+        Some(Value::Array(uris)) if uris.is_empty() => {
+            section.error("rpc_uri", "must not be empty");
+            None
+        }
+        Some(Value::Array(uris)) => uris
+            .into_iter()
+            .map(|uri| section.check("rpc_uri", Secret::try_from(uri)))
+            .collect::<Vec<Option<Secret>>>()
+            .into_iter()
+            .collect(),
+        Some(uri) => section
+            .check("rpc_uri", Secret::try_from(uri))
+            .map(|uri| vec![uri]),
+        None => {
+            section.error("rpc_uri", "missing required key");
+            None
+        }
+    }
+}
+
+/// 解析 `key` 中的合约或账户地址，无效的地址记录在 `section` 中
+fn addresses<C: Config>(section: &mut Section<C>, key: &str, addresses: &[String]) -> Vec<Address> {
+    addresses
+        .iter()
+        .filter_map(|address| {
+            let parsed = Address::from_str(address).map_err(|err| {
+                Error::invalid_param(&format!("invalid address {} - {}", address, err))
+            });
+            section.check(key, parsed)
+        })
+        .collect()
+}
+
+/// 读取 `retry` 子表，如 `[input.retry]`
+pub(crate) fn retry<C: Config>(section: &mut Section<C>) -> RetryPolicy {
+    let mut section = section.section("retry");
+    let retry = RetryPolicy::from_section(&mut section);
+    section.finish();
+    retry
+}
+
+/// 读取 `rate_limit` 子表，如 `[input.rate_limit]`，不存在时不限流
+pub(crate) fn rate_limit<C: Config>(section: &mut Section<C>) -> Option<RateLimitConfig> {
+    let mut section = section.section("rate_limit");
+    let rate_limit = if section.exists() {
+        RateLimitConfig::from_section(&mut section)
+    } else {
+        None
+    };
+    section.finish();
+    rate_limit
 }
@@ -1,32 +1,656 @@
-use super::Input;
-use crate::{Config, Result, decode::Decoder, new_runtime, process::Processor};
-use tokio::sync::mpsc::Sender;
-use web3::{transports::Http, Web3};
+use std::collections::VecDeque;
+use std::time::Duration;
 
+use async_trait::async_trait;
+use tokio::task::JoinHandle;
+use tokio::time::sleep;
+use web3::types::{Address, BlockNumber, Filter, FilterBuilder, Log, H256};
+use web3::{ethabi, Web3};
+
+use super::{Batch, Input, Record};
+use crate::config::{Secret, Section};
+use crate::decode::load_contract;
+use crate::{Config, Context, Error, RateLimitConfig, Result, RetryPolicy, RpcPool};
+
+/// `input.type = "web3_event"` 的配置
+#[derive(Debug, Clone, PartialEq)]
+pub struct Web3EventInputConfig {
+    /// RPC 节点地址，可能带有 API key；`rpc_uri` 可以是一个地址或地址数组
+    pub rpc_uris: Vec<Secret>,
+    /// 回填时同时查询的区块范围数
+    pub max_thread: usize,
+    /// 需要订阅日志的合约地址
+    pub contracts: Vec<Address>,
+    /// 合约 ABI 文件，配置 `events` 时必须提供
+    pub abi: Option<String>,
+    /// 需要订阅的事件名，为空时订阅全部事件
+    pub events: Vec<String>,
+    /// 起始区块
+    pub from_block: u64,
+    /// 结束区块（包含），读取完成后输入结束，不配置时持续读取新区块
+    pub to_block: Option<u64>,
+    /// 区块确认数，只读取 `最新区块 - confirmations` 之前的日志
+    pub confirmations: u64,
+    /// 每次 `eth_getLogs` 查询的初始区块数
+    pub batch_size: u64,
+    /// 查询范围过大或超时时缩小到的最小区块数
+    pub min_batch_size: u64,
+    /// 查询成功后扩大到的最大区块数
+    pub max_batch_size: u64,
+    /// 追上最新区块后的轮询间隔（毫秒）
+    pub poll_interval: u64,
+    /// 记录已处理区块的文件
+    pub checkpoint: Option<String>,
+    /// RPC 调用失败时的重试策略，`[input.retry]`
+    pub retry: RetryPolicy,
+    /// 每个节点的限流，`[input.rate_limit]`，不配置时不限流
+    pub rate_limit: Option<RateLimitConfig>,
+}
+
+impl Web3EventInputConfig {
+    pub fn from_section<C: Config>(section: &mut Section<C>) -> Option<Self> {
+        let rpc_uris = super::rpc_uris(section);
+        let max_thread = section.get_or("max_thread", 1_usize);
+        let contracts = section
+            .required::<Vec<String>>("contracts")
+            .map(|contracts| super::addresses(section, "contracts", &contracts));
+        let abi = section.optional::<String>("abi");
+        let events = section.get_or::<Vec<String>>("events", vec![]);
+        match &abi {
+            Some(abi) => {
+                let loaded = load_abi(abi).and_then(|contract| {
+                    events.iter().try_for_each(|event| {
+                        contract.event(event).map(|_| ()).map_err(Error::from)
+                    })
+                });
+                section.check("abi", loaded);
+            }
+            None if !events.is_empty() => section.error("events", "requires input.abi"),
+            None => {}
+        }
+        let from_block = section.get_or("from_block", 0_u64);
+        let to_block = section.optional::<u64>("to_block");
+        if to_block.is_some_and(|to_block| to_block < from_block) {
+            section.error("to_block", "must not be less than from_block");
+        }
+        let confirmations = section.get_or("confirmations", 0_u64);
+        let batch_size = section.get_or("batch_size", 1000_u64);
+        let min_batch_size = section.get_or("min_batch_size", 1_u64);
+        let max_batch_size = section.get_or("max_batch_size", batch_size);
+        if min_batch_size == 0 {
+            section.error("min_batch_size", "must be greater than 0");
+        }
+        if !(min_batch_size..=max_batch_size).contains(&batch_size) {
+            section.error(
+                "batch_size",
+                format!(
+                    "must be between min_batch_size {} and max_batch_size {}",
+                    min_batch_size, max_batch_size
+                ),
+            );
+        }
+        let poll_interval = section.get_or("poll_interval", 5000_u64);
+        let checkpoint = section.optional::<String>("checkpoint");
+        let retry = super::retry(section);
+        let rate_limit = super::rate_limit(section);
+
+        Some(Self {
+            rpc_uris: rpc_uris?,
+            max_thread,
+            contracts: contracts?,
+            abi,
+            events,
+            from_block,
+            to_block,
+            confirmations,
+            batch_size,
+            min_batch_size,
+            max_batch_size,
+            poll_interval,
+            checkpoint,
+            retry,
+            rate_limit,
+        })
+    }
+}
+
+pub(crate) fn load_abi(path: &str) -> Result<ethabi::Contract> {
+    let json = std::fs::read(path).map_err(|err| {
+        Error::new(
+            Error::from(err).get_code(),
+            &format!("can't open abi {}", path),
+        )
+    })?;
+    load_contract(&json)
+}
+
+/// 通过 `RpcPool` 轮询 `eth_getLogs` 读取合约日志，第一次读取前检查所有节点的链 ID
+///
+/// 落后于最新区块时最多同时查询 `max_thread` 个区块范围（回填），结果按区块顺序输出；
+/// 追上最新区块后每次只查询一个范围（实时跟踪）。
 pub struct Web3EventInput {
-    web3: Web3<Http>,
+    pool: RpcPool,
+    web3: Web3<RpcPool>,
+    /// 是否已经检查过链 ID
+    checked: bool,
+    config: Web3EventInputConfig,
+    /// 需要订阅的事件签名，为空时订阅全部事件
+    topics: Vec<H256>,
+    /// 下一个需要读取的区块
+    next_block: u64,
+    window: LogWindow,
+    /// 已确认的最新区块，还没有读取最新区块或没有区块达到确认数时为 `None`
+    head: Option<u64>,
+    /// 下一个需要发起查询的区块
+    scheduled: u64,
+    /// 按区块顺序排列的查询，即重排缓冲：后面的范围先完成时结果留在任务中，
+    /// 等前面的范围输出后再取出，最多缓存 `max_thread` 个范围
+    pending: VecDeque<Pending>,
+}
+
+/// 正在查询的区块范围
+struct Pending {
+    from: u64,
+    to: u64,
+    handle: JoinHandle<Result<Vec<Log>>>,
+}
+
+/// 连续成功多少次后扩大查询范围
+const GROW_AFTER: u32 = 4;
+
+/// `eth_getLogs` 的查询区块数，查询范围过大或超时时减半，
+/// 连续成功 `GROW_AFTER` 次后增加四分之一，避免在节点限制附近反复缩放
+#[derive(Debug, Clone, PartialEq)]
+struct LogWindow {
+    size: u64,
+    min: u64,
+    max: u64,
+    /// 上次缩小或扩大后连续成功的次数
+    successes: u32,
+}
+
+impl LogWindow {
+    fn new(size: u64, min: u64, max: u64) -> Self {
+        Self {
+            size,
+            min,
+            max,
+            successes: 0,
+        }
+    }
+
+    /// 已经是最小值时返回 `false`
+    fn shrink(&mut self) -> bool {
+        self.successes = 0;
+        if self.size <= self.min {
+            return false;
+        }
+        self.size = (self.size / 2).max(self.min);
+        true
+    }
+
+    fn grow(&mut self) {
+        self.successes += 1;
+        if self.successes < GROW_AFTER {
+            return;
+        }
+        self.successes = 0;
+        self.size = self
+            .size
+            .saturating_add((self.size / 4).max(1))
+            .min(self.max);
+    }
 }
 
 impl Web3EventInput {
-    fn new<C: Config>(config: &C) -> Result<Self> {
-        let rpc_uri: String = config.get_value("")?;
+    pub fn new(config: Web3EventInputConfig) -> Result<Self> {
+        let pool = RpcPool::new(&config.rpc_uris, config.rate_limit.as_ref())?;
+        let web3 = Web3::new(pool.clone());
+        let topics = match &config.abi {
+            Some(abi) if !config.events.is_empty() => {
+                let contract = load_abi(abi)?;
+                config
+                    .events
+                    .iter()
+                    .map(|event| Ok(contract.event(event)?.signature()))
+                    .collect::<Result<Vec<H256>>>()?
+            }
+            _ => vec![],
+        };
+        let next_block = config.from_block;
+        let window = LogWindow::new(
+            config.batch_size,
+            config.min_batch_size,
+            config.max_batch_size,
+        );
+        Ok(Self {
+            pool,
+            web3,
+            checked: false,
+            config,
+            topics,
+            next_block,
+            window,
+            head: None,
+            scheduled: next_block,
+            pending: VecDeque::new(),
+        })
+    }
 
-        let http = web3::transports::Http::new(&rpc_uri)?;
-        let web3 = web3::Web3::new(http);
-        return Ok(Web3EventInput { web3 });
+    /// 放弃所有正在进行的查询，之后从 `next_block` 重新查询
+    fn cancel(&mut self) {
+        for pending in self.pending.drain(..) {
+            pending.handle.abort();
+        }
+        self.scheduled = self.next_block;
+    }
+
+    fn filter(&self, from: u64, to: u64) -> Filter {
+        let topics = (!self.topics.is_empty()).then(|| self.topics.clone());
+        FilterBuilder::default()
+            .address(self.config.contracts.clone())
+            .topics(topics, None, None, None)
+            .from_block(BlockNumber::Number(from.into()))
+            .to_block(BlockNumber::Number(to.into()))
+            .build()
     }
 }
 
+#[async_trait]
 impl Input for Web3EventInput {
-    fn start<C: Config, P: Processor, D: Decoder>(
-        config: &C,
-        processor: P,
-        decoder: D,
-        sender: Sender<D>,
-    ) -> Result<()> {
-        let input = Self::new(config);
-        new_runtime(config, "input.max_thread", async {})?;
+    fn seek(&mut self, checkpoint: u64) {
+        self.next_block = checkpoint;
+        self.cancel();
+    }
+
+    fn position(&self) -> u64 {
+        self.next_block
+    }
+
+    async fn next(&mut self) -> Result<Batch> {
+        if !self.checked {
+            super::check_chain_id(&self.pool, &self.config.retry).await?;
+            self.checked = true;
+        }
+        self.fetch().await
+    }
+
+    fn end(&self) -> Option<u64> {
+        self.config.to_block
+    }
+
+    fn retry_interval(&self) -> u64 {
+        self.config.poll_interval
+    }
+}
+
+impl Web3EventInput {
+    async fn fetch(&mut self) -> Result<Batch> {
+        let mut backoff = self.config.retry.backoff();
+        loop {
+            self.schedule().await?;
+            // 只在完成后取出，`next` 被取消时不会丢失范围
+            let result = match self.pending.front_mut() {
+                Some(pending) => (&mut pending.handle).await,
+                None => {
+                    sleep(Duration::from_millis(self.config.poll_interval)).await;
+                    return Ok(Batch {
+                        records: vec![],
+                        checkpoint: self.next_block,
+                    });
+                }
+            };
+            let pending = self.pending.pop_front().unwrap();
+            let err = match result {
+                Ok(Ok(mut logs)) => {
+                    logs.sort_by_key(|log| (log.block_number, log.log_index));
+                    self.window.grow();
+                    self.next_block = pending.to + 1;
+                    return Ok(Batch {
+                        records: logs
+                            .into_iter()
+                            .map(|log| Record::Log(Box::new(log)))
+                            .collect(),
+                        checkpoint: self.next_block,
+                    });
+                }
+                Ok(Err(err)) => err,
+                Err(err) => Error::internal(&err.to_string()),
+            };
+
+            self.cancel();
+            if (err.is_range_too_large() || err.is_timeout_err()) && self.window.shrink() {
+                log::warn!(
+                    "shrink eth_getLogs window to {} blocks - {}",
+                    self.window.size,
+                    err
+                );
+                continue;
+            }
+            match backoff.next_delay(&err) {
+                Some(delay) => {
+                    log::warn!("retry in {}ms - {}", delay.as_millis(), err);
+                    sleep(delay).await;
+                }
+                None => {
+                    return Err(err.context(format!(
+                        "while fetching logs for blocks {}..={}",
+                        pending.from, pending.to
+                    )))
+                }
+            }
+        }
+    }
 
-        return Ok(());
+    /// 发起查询直到达到 `max_thread` 个或追上最新区块，已发起的查询都在最新区块之前时才重新读取最新区块
+    async fn schedule(&mut self) -> Result<()> {
+        if self.head.is_none_or(|head| self.scheduled > head) {
+            let pool = &self.pool;
+            let head = self
+                .config
+                .retry
+                .retry(|| pool.head())
+                .await
+                .context("while fetching latest block number")?;
+            self.head = head.checked_sub(self.config.confirmations).map(|head| {
+                self.config
+                    .to_block
+                    .map_or(head, |to_block| head.min(to_block))
+            });
+        }
+        let head = match self.head {
+            Some(head) => head,
+            None => return Ok(()),
+        };
+        while self.pending.len() < self.config.max_thread.max(1) && self.scheduled <= head {
+            let from = self.scheduled;
+            let to = head.min(from + self.window.size - 1);
+            let logs = self.web3.eth().logs(self.filter(from, to));
+            let handle = tokio::spawn(async move { logs.await.map_err(Error::from) });
+            self.pending.push_back(Pending { from, to, handle });
+            self.scheduled = to + 1;
+        }
+        Ok(())
     }
 }
+
+impl Drop for Web3EventInput {
+    fn drop(&mut self) {
+        self.cancel();
+    }
+}
+
+#[test]
+fn test_log_window() {
+    let mut window = LogWindow::new(1000, 100, 2000);
+    assert!(window.shrink());
+    assert_eq!(500, window.size);
+    assert!(window.shrink());
+    assert!(window.shrink());
+    assert_eq!(125, window.size);
+    assert!(window.shrink());
+    assert_eq!(100, window.size);
+    assert!(!window.shrink());
+
+    for _ in 0..GROW_AFTER - 1 {
+        window.grow();
+    }
+    assert_eq!(100, window.size);
+    window.grow();
+    assert_eq!(125, window.size);
+    for _ in 0..GROW_AFTER {
+        window.grow();
+    }
+    assert_eq!(156, window.size);
+
+    // 缩小后重新计数
+    for _ in 0..GROW_AFTER - 1 {
+        window.grow();
+    }
+    assert!(window.shrink());
+    window.grow();
+    assert_eq!(100, window.size);
+
+    let mut window = LogWindow::new(1, 1, 1900);
+    for _ in 0..GROW_AFTER {
+        window.grow();
+    }
+    assert_eq!(2, window.size);
+    for _ in 0..100 * GROW_AFTER {
+        window.grow();
+    }
+    assert_eq!(1900, window.size);
+}
+
+#[cfg(test)]
+fn test_config(rpc_uri: &str) -> Web3EventInputConfig {
+    Web3EventInputConfig {
+        rpc_uris: vec![Secret::new(rpc_uri)],
+        max_thread: 4,
+        contracts: vec![],
+        abi: None,
+        events: vec![],
+        from_block: 0,
+        to_block: None,
+        confirmations: 0,
+        batch_size: 10,
+        min_batch_size: 1,
+        max_batch_size: 10,
+        poll_interval: 10,
+        checkpoint: None,
+        retry: RetryPolicy::default(),
+        rate_limit: None,
+    }
+}
+
+/// 每个区块两条日志，倒序返回
+#[cfg(test)]
+fn test_logs(from: u64, to: u64) -> serde_json::Value {
+    use crate::rpc::mock::quantity;
+    (from..=to)
+        .rev()
+        .flat_map(|block| {
+            (0..2).rev().map(move |index| {
+                serde_json::json!({
+                    "address": format!("{:?}", Address::zero()),
+                    "topics": [],
+                    "data": "0x",
+                    "blockNumber": quantity(block),
+                    "logIndex": quantity(index),
+                })
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+async fn read_blocks(input: &mut Web3EventInput, count: usize) -> Vec<(u64, u64)> {
+    let mut logs = vec![];
+    while logs.len() < count {
+        let batch = input.next().await.unwrap();
+        for record in batch.records {
+            if let Record::Log(log) = record {
+                let block = log.block_number.unwrap().as_u64();
+                logs.push((block, log.log_index.unwrap().as_u64()));
+                assert!(block < batch.checkpoint);
+            }
+        }
+    }
+    logs
+}
+
+#[tokio::test]
+async fn test_backfill() {
+    use crate::rpc::mock::{parse_quantity, quantity, serve, Reply};
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::Arc;
+
+    let head = Arc::new(AtomicU64::new(100));
+    let uri = serve({
+        let head = head.clone();
+        move |method, params| {
+            let head = head.load(Ordering::SeqCst);
+            async move {
+                match method.as_str() {
+                    "eth_chainId" => Reply::Result(quantity(1)),
+                    "eth_blockNumber" => Reply::Result(quantity(head)),
+                    "eth_getLogs" => {
+                        let from = parse_quantity(&params[0]["fromBlock"]);
+                        let to = parse_quantity(&params[0]["toBlock"]);
+                        // 越靠前的范围越晚返回，打乱完成顺序
+                        sleep(Duration::from_millis(100_u64.saturating_sub(from) / 5)).await;
+                        Reply::Result(test_logs(from, to))
+                    }
+                    _ => Reply::Error(-32601, "method not found".to_owned()),
+                }
+            }
+        }
+    })
+    .await;
+
+    let mut input = Web3EventInput::new(test_config(&uri)).unwrap();
+    let logs = read_blocks(&mut input, 202).await;
+    let expected: Vec<(u64, u64)> = (0..=100)
+        .flat_map(|block| (0..2).map(move |index| (block, index)))
+        .collect();
+    assert_eq!(expected, logs);
+
+    let batch = input.next().await.unwrap();
+    assert!(batch.records.is_empty());
+    assert_eq!(101, batch.checkpoint);
+
+    head.store(115, Ordering::SeqCst);
+    let logs = read_blocks(&mut input, 30).await;
+    assert_eq!((101, 0), logs[0]);
+    assert_eq!((115, 1), logs[29]);
+    assert_eq!(116, input.position());
+}
+
+#[tokio::test]
+async fn test_shrink_window() {
+    use crate::rpc::mock::{parse_quantity, quantity, serve, Reply};
+
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+
+    let limited = Arc::new(AtomicBool::new(true));
+    let uri = serve(move |method, params| {
+        let limited = limited.swap(false, Ordering::SeqCst);
+        async move {
+            match method.as_str() {
+                "eth_chainId" if limited => Reply::Status(429),
+                "eth_chainId" => Reply::Result(quantity(1)),
+                "eth_blockNumber" => Reply::Result(quantity(20)),
+                "eth_getLogs" => {
+                    let from = parse_quantity(&params[0]["fromBlock"]);
+                    let to = parse_quantity(&params[0]["toBlock"]);
+                    if to - from >= 3 {
+                        Reply::Error(-32005, "query returned more than 10000 results".to_owned())
+                    } else {
+                        Reply::Result(test_logs(from, to))
+                    }
+                }
+                _ => Reply::Error(-32601, "method not found".to_owned()),
+            }
+        }
+    })
+    .await;
+
+    let mut config = test_config(&uri);
+    config.retry.initial_interval = 1;
+    let mut input = Web3EventInput::new(config).unwrap();
+    let logs = read_blocks(&mut input, 42).await;
+    let blocks: Vec<u64> = logs.iter().step_by(2).map(|(block, _)| *block).collect();
+    assert_eq!((0..=20).collect::<Vec<u64>>(), blocks);
+    assert!(input.window.size < 10);
+}
+
+#[tokio::test]
+async fn test_shrink_then_grow_window() {
+    use crate::rpc::mock::{parse_quantity, quantity, serve, Reply};
+    use std::sync::{Arc, Mutex};
+
+    // 节点最多接受 3 个区块的查询
+    let sizes = Arc::new(Mutex::new(vec![]));
+    let uri = serve({
+        let sizes = sizes.clone();
+        move |method, params| {
+            let reply = match method.as_str() {
+                "eth_chainId" => Reply::Result(quantity(1)),
+                "eth_blockNumber" => Reply::Result(quantity(59)),
+                "eth_getLogs" => {
+                    let from = parse_quantity(&params[0]["fromBlock"]);
+                    let to = parse_quantity(&params[0]["toBlock"]);
+                    sizes.lock().unwrap().push(to - from + 1);
+                    if to - from >= 3 {
+                        Reply::Error(-32005, "query returned more than 10000 results".to_owned())
+                    } else {
+                        Reply::Result(test_logs(from, to))
+                    }
+                }
+                _ => Reply::Error(-32601, "method not found".to_owned()),
+            };
+            async move { reply }
+        }
+    })
+    .await;
+
+    let mut config = test_config(&uri);
+    config.max_thread = 1;
+    let mut input = Web3EventInput::new(config).unwrap();
+    let logs = read_blocks(&mut input, 120).await;
+    let blocks: Vec<u64> = logs.iter().step_by(2).map(|(block, _)| *block).collect();
+    assert_eq!((0..60).collect::<Vec<u64>>(), blocks);
+
+    // 10 与 5 失败后缩小到 2，之后逐步扩大，到 4 时再次失败，不会每隔一次查询就失败
+    let cycle = [2, 2, 2, 2, 3, 3, 3, 3, 4];
+    let expected: Vec<u64> = [10, 5]
+        .into_iter()
+        .chain(cycle.into_iter().cycle())
+        .take(28)
+        .collect();
+    assert_eq!(expected, sizes.lock().unwrap()[..28]);
+}
+
+#[tokio::test]
+async fn test_confirmations_from_genesis() {
+    use crate::rpc::mock::{parse_quantity, quantity, serve, Reply};
+    use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    let head = Arc::new(AtomicU64::new(1));
+    let queries = Arc::new(AtomicUsize::new(0));
+    let uri = serve({
+        let head = head.clone();
+        let queries = queries.clone();
+        move |method, params| {
+            let reply = match method.as_str() {
+                "eth_chainId" => Reply::Result(quantity(1)),
+                "eth_blockNumber" => Reply::Result(quantity(head.load(Ordering::SeqCst))),
+                "eth_getLogs" => {
+                    queries.fetch_add(1, Ordering::SeqCst);
+                    let from = parse_quantity(&params[0]["fromBlock"]);
+                    let to = parse_quantity(&params[0]["toBlock"]);
+                    Reply::Result(test_logs(from, to))
+                }
+                _ => Reply::Error(-32601, "method not found".to_owned()),
+            };
+            async move { reply }
+        }
+    })
+    .await;
+
+    let mut config = test_config(&uri);
+    config.confirmations = 2;
+    let mut input = Web3EventInput::new(config).unwrap();
+    // 区块 0 还没有达到确认数
+    let batch = input.next().await.unwrap();
+    assert!(batch.records.is_empty());
+    assert_eq!(0, batch.checkpoint);
+    assert_eq!(0, queries.load(Ordering::SeqCst));
+
+    head.store(5, Ordering::SeqCst);
+    let logs = read_blocks(&mut input, 8).await;
+    assert_eq!((3, 1), logs[7]);
+    assert_eq!(4, input.position());
+}
@@ -0,0 +1,579 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use tokio::time::sleep;
+use web3::futures::stream::{self, StreamExt, TryStreamExt};
+use web3::types::{Address, Block, BlockId, Transaction, U256};
+use web3::Web3;
+
+use super::web3_event::load_abi;
+use super::{Batch, Input, Record};
+use crate::config::{Secret, Section};
+use crate::decode::CallDecoder;
+use crate::value::Bytes;
+use crate::{Config, Context, Error, Event, RateLimitConfig, Result, RetryPolicy, RpcPool, Value};
+
+/// `input.type = "web3_rpc"` 的配置
+#[derive(Debug, Clone, PartialEq)]
+pub struct Web3RpcInputConfig {
+    /// RPC 节点地址，可能带有 API key；`rpc_uri` 可以是一个地址或地址数组
+    pub rpc_uris: Vec<Secret>,
+    /// 同时查询的区块数
+    pub max_thread: usize,
+    /// 起始区块
+    pub from_block: u64,
+    /// 结束区块（包含），读取完成后输入结束，不配置时持续读取新区块
+    pub to_block: Option<u64>,
+    /// 区块确认数，只读取 `最新区块 - confirmations` 之前的区块
+    pub confirmations: u64,
+    /// 每批读取的区块数
+    pub batch_size: u64,
+    /// 追上最新区块后的轮询间隔（毫秒）
+    pub poll_interval: u64,
+    /// 记录已处理区块的文件
+    pub checkpoint: Option<String>,
+    /// 是否输出交易事件
+    pub transactions: bool,
+    /// 只输出发送到这些地址的交易，为空时不过滤
+    pub to: Vec<Address>,
+    /// 只输出由这些地址发送的交易，为空时不过滤
+    pub from: Vec<Address>,
+    /// 合约 ABI 文件，配置后按 ABI 解码交易的调用数据
+    pub abi: Option<String>,
+    /// RPC 调用失败时的重试策略，`[input.retry]`
+    pub retry: RetryPolicy,
+    /// 每个节点的限流，`[input.rate_limit]`，不配置时不限流
+    pub rate_limit: Option<RateLimitConfig>,
+}
+
+impl Web3RpcInputConfig {
+    pub fn from_section<C: Config>(section: &mut Section<C>) -> Option<Self> {
+        let rpc_uris = super::rpc_uris(section);
+        let max_thread = section.get_or("max_thread", 1_usize);
+        let from_block = section.get_or("from_block", 0_u64);
+        let to_block = section.optional::<u64>("to_block");
+        if to_block.is_some_and(|to_block| to_block < from_block) {
+            section.error("to_block", "must not be less than from_block");
+        }
+        let confirmations = section.get_or("confirmations", 0_u64);
+        let batch_size = section.get_or("batch_size", 10_u64);
+        if batch_size == 0 {
+            section.error("batch_size", "must be greater than 0");
+        }
+        let poll_interval = section.get_or("poll_interval", 5000_u64);
+        let checkpoint = section.optional::<String>("checkpoint");
+        let transactions = section.get_or("transactions", false);
+        let to = section.get_or::<Vec<String>>("to", vec![]);
+        let to = super::addresses(section, "to", &to);
+        let from = section.get_or::<Vec<String>>("from", vec![]);
+        let from = super::addresses(section, "from", &from);
+        let abi = section.optional::<String>("abi");
+        if let Some(abi) = &abi {
+            section.check("abi", load_abi(abi));
+        }
+        if !transactions {
+            for (key, used) in [("to", !to.is_empty()), ("from", !from.is_empty())] {
+                if used {
+                    section.error(key, "requires input.transactions = true");
+                }
+            }
+            if abi.is_some() {
+                section.error("abi", "requires input.transactions = true");
+            }
+        }
+        let retry = super::retry(section);
+        let rate_limit = super::rate_limit(section);
+
+        Some(Self {
+            rpc_uris: rpc_uris?,
+            max_thread,
+            from_block,
+            to_block,
+            confirmations,
+            batch_size,
+            poll_interval,
+            checkpoint,
+            transactions,
+            to,
+            from,
+            abi,
+            retry,
+            rate_limit,
+        })
+    }
+}
+
+/// 通过 `RpcPool` 按区块顺序读取区块，每个区块输出一个 `type = "block"` 的事件，
+/// 开启 `transactions` 后再输出区块中每笔交易的 `type = "transaction"` 事件
+///
+/// 检查点与区块确认数的含义与 `Web3EventInput` 相同。
+pub struct Web3RpcInput {
+    pool: RpcPool,
+    web3: Web3<RpcPool>,
+    /// 是否已经检查过链 ID
+    checked: bool,
+    config: Web3RpcInputConfig,
+    /// 按 `abi` 解码调用数据
+    calls: Option<CallDecoder>,
+    /// 下一个需要读取的区块
+    next_block: u64,
+    /// 已确认的最新区块，还没有读取最新区块或没有区块达到确认数时为 `None`
+    head: Option<u64>,
+}
+
+impl Web3RpcInput {
+    pub fn new(config: Web3RpcInputConfig) -> Result<Self> {
+        let pool = RpcPool::new(&config.rpc_uris, config.rate_limit.as_ref())?;
+        let web3 = Web3::new(pool.clone());
+        let calls = match &config.abi {
+            Some(abi) => Some(CallDecoder::new(&load_abi(abi)?)),
+            None => None,
+        };
+        Ok(Self {
+            pool,
+            web3,
+            checked: false,
+            next_block: config.from_block,
+            config,
+            calls,
+            head: None,
+        })
+    }
+
+    /// 读取一个区块，开启 `transactions` 时包含完整的交易
+    async fn block(&self, number: u64) -> Result<(Event, Vec<Transaction>)> {
+        let eth = self.web3.eth();
+        let id = BlockId::Number(number.into());
+        let unknown = || Error::unknown_block(&format!("block {} not found", number));
+        let (event, transactions) = if self.config.transactions {
+            let block = self
+                .config
+                .retry
+                .retry(|| async { eth.block_with_txs(id).await?.ok_or_else(unknown) })
+                .await?;
+            (block_event(&block), block.transactions)
+        } else {
+            let block = self
+                .config
+                .retry
+                .retry(|| async { eth.block(id).await?.ok_or_else(unknown) })
+                .await?;
+            (block_event(&block), vec![])
+        };
+        Ok((event, transactions))
+    }
+
+    fn accepts(&self, transaction: &Transaction) -> bool {
+        let matches = |filter: &[Address], address: Option<Address>| {
+            filter.is_empty() || address.is_some_and(|address| filter.contains(&address))
+        };
+        matches(&self.config.to, transaction.to) && matches(&self.config.from, transaction.from)
+    }
+
+    fn transaction_event(&self, block: &Event, transaction: Transaction) -> Event {
+        let mut event = Event::new();
+        event.insert("type".to_owned(), Value::from("transaction"));
+        for key in ["block_number", "block_hash", "timestamp"] {
+            event.insert(key.to_owned(), block[key].clone());
+        }
+        event.insert(
+            "transaction_hash".to_owned(),
+            Value::from(format!("{:?}", transaction.hash)),
+        );
+        event.insert(
+            "transaction_index".to_owned(),
+            Value::from(
+                transaction
+                    .transaction_index
+                    .map(|index| index.as_u64() as i64),
+            ),
+        );
+        event.insert(
+            "from".to_owned(),
+            Value::from(transaction.from.map(|from| format!("{:?}", from))),
+        );
+        event.insert(
+            "to".to_owned(),
+            Value::from(transaction.to.map(|to| format!("{:?}", to))),
+        );
+        event.insert(
+            "value".to_owned(),
+            Value::from(transaction.value.to_string()),
+        );
+        event.insert("gas".to_owned(), integer(transaction.gas));
+        event.insert(
+            "gas_price".to_owned(),
+            Value::from(transaction.gas_price.to_string()),
+        );
+        event.insert("nonce".to_owned(), integer(transaction.nonce));
+        // 调用参数加上 `arg_` 前缀，避免与交易的 `from`、`to` 等字段冲突
+        match self
+            .calls
+            .as_ref()
+            .map(|calls| calls.decode(&transaction.input.0))
+        {
+            Some(Ok(Some(call))) => {
+                for (name, value) in call {
+                    let name = match name.as_str() {
+                        "function" => name,
+                        _ => format!("arg_{}", name),
+                    };
+                    event.insert(name, value);
+                }
+            }
+            Some(Err(err)) => log::warn!("{} in transaction {:?}", err, transaction.hash),
+            Some(Ok(None)) | None => {}
+        }
+        event.insert("input".to_owned(), Value::Bytes(Bytes(transaction.input.0)));
+        event
+    }
+}
+
+#[async_trait]
+impl Input for Web3RpcInput {
+    fn seek(&mut self, checkpoint: u64) {
+        self.next_block = checkpoint;
+    }
+
+    fn position(&self) -> u64 {
+        self.next_block
+    }
+
+    async fn next(&mut self) -> Result<Batch> {
+        if !self.checked {
+            super::check_chain_id(&self.pool, &self.config.retry).await?;
+            self.checked = true;
+        }
+        if self.head.is_none_or(|head| self.next_block > head) {
+            let pool = &self.pool;
+            let head = self
+                .config
+                .retry
+                .retry(|| pool.head())
+                .await
+                .context("while fetching latest block number")?;
+            self.head = head.checked_sub(self.config.confirmations).map(|head| {
+                self.config
+                    .to_block
+                    .map_or(head, |to_block| head.min(to_block))
+            });
+        }
+        let head = match self.head {
+            Some(head) if self.next_block <= head => head,
+            _ => {
+                sleep(Duration::from_millis(self.config.poll_interval)).await;
+                return Ok(Batch {
+                    records: vec![],
+                    checkpoint: self.next_block,
+                });
+            }
+        };
+
+        let from = self.next_block;
+        let to = head.min(from + self.config.batch_size - 1);
+        let blocks: Vec<(Event, Vec<Transaction>)> = stream::iter(from..=to)
+            .map(|number| self.block(number))
+            .buffered(self.config.max_thread.max(1))
+            .try_collect()
+            .await
+            .with_context(|| format!("while fetching blocks {}..={}", from, to))?;
+
+        let mut records = vec![];
+        for (block, transactions) in blocks {
+            let transactions: Vec<Event> = transactions
+                .into_iter()
+                .filter(|transaction| self.accepts(transaction))
+                .map(|transaction| self.transaction_event(&block, transaction))
+                .collect();
+            records.push(Record::Event(block));
+            records.extend(transactions.into_iter().map(Record::Event));
+        }
+        self.next_block = to + 1;
+        Ok(Batch {
+            records,
+            checkpoint: self.next_block,
+        })
+    }
+
+    fn end(&self) -> Option<u64> {
+        self.config.to_block
+    }
+
+    fn retry_interval(&self) -> u64 {
+        self.config.poll_interval
+    }
+}
+
+fn block_event<TX>(block: &Block<TX>) -> Event {
+    let mut event = Event::new();
+    event.insert("type".to_owned(), Value::from("block"));
+    event.insert(
+        "block_number".to_owned(),
+        Value::from(block.number.map(|number| number.as_u64() as i64)),
+    );
+    event.insert(
+        "block_hash".to_owned(),
+        Value::from(block.hash.map(|hash| format!("{:?}", hash))),
+    );
+    event.insert("timestamp".to_owned(), integer(block.timestamp));
+    event.insert("gas_used".to_owned(), integer(block.gas_used));
+    event.insert("gas_limit".to_owned(), integer(block.gas_limit));
+    event.insert(
+        "base_fee_per_gas".to_owned(),
+        Value::from(block.base_fee_per_gas.map(|fee| fee.to_string())),
+    );
+    event.insert(
+        "miner".to_owned(),
+        Value::from(format!("{:?}", block.author)),
+    );
+    event.insert(
+        "transaction_count".to_owned(),
+        Value::from(block.transactions.len() as i64),
+    );
+    event
+}
+
+/// 不会超出 i64 的数量，如时间戳、gas、nonce；金额使用十进制字符串
+fn integer(value: U256) -> Value {
+    Value::Integer(value.low_u64() as i64)
+}
+
+#[cfg(test)]
+fn test_config(rpc_uri: &str) -> Web3RpcInputConfig {
+    Web3RpcInputConfig {
+        rpc_uris: vec![Secret::new(rpc_uri)],
+        max_thread: 4,
+        from_block: 0,
+        to_block: None,
+        confirmations: 2,
+        batch_size: 4,
+        poll_interval: 10,
+        checkpoint: None,
+        transactions: false,
+        to: vec![],
+        from: vec![],
+        abi: None,
+        retry: RetryPolicy::default(),
+        rate_limit: None,
+    }
+}
+
+/// 每个区块两笔交易：区块号为 0x01 发给 0x0a 的 mintWithURI 调用，与 0x02 发给 0x0b 的转账
+#[cfg(test)]
+pub(crate) fn test_block(number: u64, full: bool) -> serde_json::Value {
+    use crate::rpc::mock::quantity;
+    use web3::types::H256;
+
+    let hash = |value: u64| format!("{:?}", H256::from_low_u64_be(value));
+    let address = |value: u64| format!("{:?}", Address::from_low_u64_be(value));
+    let contract = load_abi("abi/AuthToken.json").unwrap();
+    let mint = contract.function("mintWithURI").unwrap();
+    let calldata = mint
+        .encode_input(&[
+            web3::ethabi::Token::Address(Address::from_low_u64_be(3)),
+            web3::ethabi::Token::Uint(number.into()),
+            web3::ethabi::Token::String(format!("ipfs://{}", number)),
+        ])
+        .unwrap();
+    let transactions: Vec<serde_json::Value> = [(1, 0x0a, calldata), (2, 0x0b, vec![])]
+        .into_iter()
+        .enumerate()
+        .map(|(index, (from, to, input))| {
+            if !full {
+                return serde_json::json!(hash(number * 10 + index as u64));
+            }
+            serde_json::json!({
+                "hash": hash(number * 10 + index as u64),
+                "nonce": quantity(number),
+                "blockHash": hash(number),
+                "blockNumber": quantity(number),
+                "transactionIndex": quantity(index as u64),
+                "from": address(from),
+                "to": address(to),
+                "value": "0xde0b6b3a7640000",
+                "gasPrice": quantity(1_000_000_000),
+                "gas": quantity(21_000),
+                "input": format!("0x{}", input.iter().map(|byte| format!("{:02x}", byte)).collect::<String>()),
+            })
+        })
+        .collect();
+    serde_json::json!({
+        "hash": hash(number),
+        "parentHash": hash(number.saturating_sub(1)),
+        "sha3Uncles": hash(0),
+        "miner": address(0xff),
+        "stateRoot": hash(0),
+        "transactionsRoot": hash(0),
+        "receiptsRoot": hash(0),
+        "number": quantity(number),
+        "gasUsed": quantity(42_000),
+        "gasLimit": quantity(30_000_000),
+        "baseFeePerGas": quantity(7),
+        "extraData": "0x",
+        "timestamp": quantity(1_600_000_000 + number * 12),
+        "difficulty": "0x0",
+        "uncles": [],
+        "transactions": transactions,
+    })
+}
+
+#[cfg(test)]
+async fn test_server() -> String {
+    use crate::rpc::mock::{parse_quantity, quantity, serve, Reply};
+
+    serve(|method, params| async move {
+        match method.as_str() {
+            "eth_chainId" => Reply::Result(quantity(1)),
+            "eth_blockNumber" => Reply::Result(quantity(11)),
+            "eth_getBlockByNumber" => {
+                let number = parse_quantity(&params[0]);
+                // 越靠前的区块越晚返回，打乱完成顺序
+                sleep(Duration::from_millis(10_u64.saturating_sub(number) * 2)).await;
+                Reply::Result(test_block(number, params[1].as_bool().unwrap_or_default()))
+            }
+            _ => Reply::Error(-32601, "method not found".to_owned()),
+        }
+    })
+    .await
+}
+
+#[tokio::test]
+async fn test_blocks() {
+    let uri = test_server().await;
+    let mut input = Web3RpcInput::new(test_config(&uri)).unwrap();
+    input.seek(3);
+
+    let mut blocks = vec![];
+    while input.position() <= 9 {
+        let batch = input.next().await.unwrap();
+        for record in batch.records {
+            let Record::Event(event) = record else {
+                panic!("expects event");
+            };
+            assert_eq!(Value::from("block"), event["type"]);
+            blocks.push(event);
+        }
+        assert_eq!(input.position(), batch.checkpoint);
+    }
+    let numbers: Vec<&Value> = blocks.iter().map(|block| &block["block_number"]).collect();
+    assert_eq!(
+        (3..=9).map(Value::Integer).collect::<Vec<_>>(),
+        numbers.into_iter().cloned().collect::<Vec<_>>()
+    );
+    assert_eq!(Value::Integer(42_000), blocks[0]["gas_used"]);
+    assert_eq!(Value::from("7"), blocks[0]["base_fee_per_gas"]);
+    assert_eq!(Value::Integer(1_600_000_036), blocks[0]["timestamp"]);
+    assert_eq!(Value::Integer(2), blocks[0]["transaction_count"]);
+    assert_eq!(
+        Value::from(format!("{:?}", Address::from_low_u64_be(0xff))),
+        blocks[0]["miner"]
+    );
+
+    // 最新区块 11 减去 2 个确认，已经追上
+    let batch = input.next().await.unwrap();
+    assert!(batch.records.is_empty());
+    assert_eq!(10, batch.checkpoint);
+
+    // 只读取到结束区块
+    let mut config = test_config(&uri);
+    config.to_block = Some(4);
+    let mut input = Web3RpcInput::new(config).unwrap();
+    input.seek(3);
+    assert_eq!(Some(4), input.end());
+    let batch = input.next().await.unwrap();
+    assert_eq!((2, 5), (batch.records.len(), batch.checkpoint));
+}
+
+#[tokio::test]
+async fn test_transactions() {
+    let uri = test_server().await;
+    let mut config = test_config(&uri);
+    config.transactions = true;
+    config.to = vec![Address::from_low_u64_be(0x0a)];
+    config.abi = Some("abi/AuthToken.json".to_owned());
+    let mut input = Web3RpcInput::new(config).unwrap();
+    input.seek(5);
+
+    let batch = input.next().await.unwrap();
+    assert_eq!(9, batch.checkpoint);
+    let events: Vec<Event> = batch
+        .records
+        .into_iter()
+        .map(|record| match record {
+            Record::Event(event) => event,
+            Record::Log(_) => panic!("expects event"),
+        })
+        .collect();
+    let kinds: Vec<&Value> = events.iter().map(|event| &event["type"]).collect();
+    let (block, transaction) = (Value::from("block"), Value::from("transaction"));
+    assert_eq!(
+        vec![
+            &block,
+            &transaction,
+            &block,
+            &transaction,
+            &block,
+            &transaction,
+            &block,
+            &transaction
+        ],
+        kinds
+    );
+
+    let mint = &events[1];
+    assert_eq!(Value::Integer(5), mint["block_number"]);
+    assert_eq!(events[0]["timestamp"], mint["timestamp"]);
+    assert_eq!(
+        Value::from(format!("{:?}", Address::from_low_u64_be(0x0a))),
+        mint["to"]
+    );
+    assert_eq!(Value::from("1000000000000000000"), mint["value"]);
+    assert_eq!(Value::Integer(21_000), mint["gas"]);
+    assert_eq!(Value::from("mintWithURI"), mint["function"]);
+    assert_eq!(
+        Value::from(format!("{:?}", Address::from_low_u64_be(3))),
+        mint["arg_to"]
+    );
+    assert_eq!(Value::from("5"), mint["arg_id"]);
+    assert_eq!(Value::from("ipfs://5"), mint["arg_tokenURI"]);
+}
+
+#[tokio::test]
+async fn test_confirmations_from_genesis() {
+    use crate::rpc::mock::{parse_quantity, quantity, serve, Reply};
+    use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    let head = Arc::new(AtomicU64::new(1));
+    let queries = Arc::new(AtomicUsize::new(0));
+    let uri = serve({
+        let head = head.clone();
+        let queries = queries.clone();
+        move |method, params| {
+            let reply = match method.as_str() {
+                "eth_chainId" => Reply::Result(quantity(1)),
+                "eth_blockNumber" => Reply::Result(quantity(head.load(Ordering::SeqCst))),
+                "eth_getBlockByNumber" => {
+                    queries.fetch_add(1, Ordering::SeqCst);
+                    Reply::Result(test_block(parse_quantity(&params[0]), false))
+                }
+                _ => Reply::Error(-32601, "method not found".to_owned()),
+            };
+            async move { reply }
+        }
+    })
+    .await;
+
+    // 最新区块 1 减去 2 个确认，区块 0 还没有确认
+    let mut input = Web3RpcInput::new(test_config(&uri)).unwrap();
+    let batch = input.next().await.unwrap();
+    assert!(batch.records.is_empty());
+    assert_eq!(0, batch.checkpoint);
+    assert_eq!(0, queries.load(Ordering::SeqCst));
+
+    head.store(2, Ordering::SeqCst);
+    let batch = input.next().await.unwrap();
+    assert_eq!(1, batch.records.len());
+    assert_eq!(1, batch.checkpoint);
+    assert_eq!(1, queries.load(Ordering::SeqCst));
+}
@@ -1,8 +1,6 @@
-use std::future::Future;
+use std::path::Path;
 
-use input::Input;
-use input::web3_event::Web3EventInput;
-use output::Output;
+extern crate self as producer;
 
 mod config;
 mod datatype;
@@ -10,44 +8,126 @@ mod decode;
 mod error;
 mod event;
 mod input;
+mod metrics;
 mod output;
-mod value;
+mod pipeline;
 mod process;
+mod retry;
+mod rpc;
+mod schema;
+mod value;
 
-pub use config::Config;
+pub use config::{decrypt_keystore, Secret, KEYSTORE_PASSWORD_ENV};
+pub use config::{
+    find_path, Config, ConfigPaths, FileConfig, Format, JsonConfig, LayeredConfig, TomlConfig,
+    YamlConfig, CONFIG_ENV, CONFIG_FILES, ENV_PREFIX,
+};
 pub use datatype::DataType;
 pub use datatype::*;
+pub use decode::{CallDecoder, Decoder, DecoderConfig, Revert, RevertDecoder};
+pub use error::Context;
 pub use error::Error;
 pub use error::Result;
-pub use event::ToEvent;
-use output::console::ConsoleOutput;
-use tokio::runtime::Builder;
-use tokio::task::JoinHandle;
+pub use event::Event;
+pub use event::{FromEvent, ToEvent};
+pub use metrics::Metrics;
+pub use pipeline::{Pipeline, PipelineConfig};
+pub use process::validate::{ValidateMode, ValidateProcessor, DEAD_LETTER_ERROR};
+pub use process::Processor;
+pub use producer_derive::{FromEvent, ToEvent};
+pub use retry::{Backoff, RetryPolicy};
+pub use rpc::{RateLimitConfig, RateLimiter, RpcPool};
+pub use schema::{EventSchema, FieldSchema};
+use tokio::runtime::{Builder, Runtime};
 pub use value::Value;
-pub fn run<C: Config>(config: &C) -> Result<()> {
-    let buffer_size: i32 = config.get_value("buffer_size")?;
-    let (mut sender, reciver) = tokio::sync::mpsc::channel(buffer_size as usize);
-
-    // Web3EventInput::start(config, processor, decoder, sender)?;
-    ConsoleOutput::start(config, reciver)?;
-    
-    Ok(())
-}
 
-fn new_runtime<C: Config, K: Into<String>, F>(
-    config: &C,
-    key: K,
-    future: F,
-) -> Result<JoinHandle<F::Output>>
+/// 通过 `load` 读取配置运行管道，直到收到 Ctrl-C
+///
+/// 运行中监视配置文件 `path`，文件修改后重新调用 `load` 并只重建配置发生变化的阶段。
+pub fn run<P, F>(path: P, load: F) -> Result<()>
 where
-    F: Future + Send + 'static,
-    F::Output: Send + 'static,
+    P: AsRef<Path>,
+    F: Fn() -> Result<PipelineConfig>,
 {
-    let max_thread: i32 = config.get_value(key)?;
+    let config = load()?;
+    new_runtime(&config)?.block_on(async {
+        let mut pipeline = Pipeline::start(config)?;
+        tokio::select! {
+            result = tokio::signal::ctrl_c() => result?,
+            _ = pipeline::watch(&mut pipeline, path, pipeline::WATCH_INTERVAL, load) => {}
+        }
+        pipeline.stop().await
+    })
+}
+
+/// 按配置回填 `from..=to` 的区块，读取完成并写出所有事件后返回，不读写检查点文件
+pub fn backfill<C: Config>(config: &C, from: u64, to: u64) -> Result<()> {
+    let mut config = PipelineConfig::from_config(config)?;
+    config.input.set_range(from, to)?;
+    new_runtime(&config)?.block_on(async {
+        let mut pipeline = Pipeline::start(config)?;
+        tokio::select! {
+            result = tokio::signal::ctrl_c() => result?,
+            _ = pipeline.finished() => {}
+        }
+        pipeline.stop().await
+    })
+}
 
-    let runtime = Builder::new_multi_thread()
+/// 输入与输出共用一个运行时，线程数为两者之和
+fn new_runtime(config: &PipelineConfig) -> Result<Runtime> {
+    let threads = config.input.max_thread() + config.output.max_thread();
+    Ok(Builder::new_multi_thread()
         .enable_all()
-        .max_blocking_threads(max_thread as usize)
-        .build()?;
-    return Ok(runtime.spawn(future));
+        .worker_threads(threads.max(1))
+        .build()?)
+}
+
+#[test]
+fn test_backfill() {
+    use crate::rpc::mock::{parse_quantity, quantity, serve, Reply};
+    use std::sync::{mpsc, Arc, Mutex};
+    use std::time::Duration;
+
+    // 模拟节点运行在另一个运行时中，`backfill` 使用自己的运行时
+    let server = Builder::new_multi_thread()
+        .worker_threads(1)
+        .enable_all()
+        .build()
+        .unwrap();
+    let blocks = Arc::new(Mutex::new(vec![]));
+    let uri = server.block_on(serve({
+        let blocks = blocks.clone();
+        move |method, params| {
+            let reply = match method.as_str() {
+                "eth_chainId" => Reply::Result(quantity(1)),
+                "eth_blockNumber" => Reply::Result(quantity(100)),
+                "eth_getBlockByNumber" => {
+                    let number = parse_quantity(&params[0]);
+                    blocks.lock().unwrap().push(number);
+                    let full = params[1].as_bool().unwrap_or_default();
+                    Reply::Result(input::web3_rpc::test_block(number, full))
+                }
+                _ => Reply::Error(-32601, "method not found".to_owned()),
+            };
+            async move { reply }
+        }
+    }));
+
+    let toml = format!(
+        "[input]\ntype = \"web3_rpc\"\nrpc_uri = \"{}\"\n\n\
+         [decoder]\ntype = \"transfer\"\n\n[output]\ntype = \"console\"\n",
+        uri
+    );
+    let config = TomlConfig::from_string(&toml).unwrap();
+    let (sender, reciver) = mpsc::channel();
+    std::thread::spawn(move || sender.send(backfill(&config, 3, 25)));
+    reciver
+        .recv_timeout(Duration::from_secs(10))
+        .expect("backfill returns after reading the last block")
+        .unwrap();
+
+    let mut blocks = blocks.lock().unwrap().clone();
+    blocks.sort_unstable();
+    assert_eq!((3..=25).collect::<Vec<u64>>(), blocks);
 }
@@ -1,38 +1,236 @@
-use std::ops::Add;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 
-use hex_literal::hex;
+use producer::{
+    find_path, CallDecoder, Error, Event, FileConfig, LayeredConfig, PipelineConfig, Result,
+    RevertDecoder, Value,
+};
 use web3::{
-    contract::{tokens::Detokenize, Contract, Options},
+    contract::{Contract, Options},
     ethabi::Token,
-    types::{Address, Bytes, CallRequest, U256},
+    types::{Address, Bytes, CallRequest, Log},
 };
 
-#[tokio::main]
-async fn main() -> web3::contract::Result<()> {
+const USAGE: &str = "usage: producer <command> [options]
+
+commands:
+  run            run the pipeline until Ctrl-C, reloading the config file when it changes
+  check-config   validate the config file without starting the pipeline
+  decode         decode a log or transaction JSON read from stdin
+  backfill       read blocks --from N --to M once, without touching the checkpoint
+  mint           call batchMintWithURI, minting one token to each node account
+
+options:
+  --config PATH        config file, defaults to $PRODUCER_CONFIG or producer.{toml,yaml,yml,json} (run, check-config, decode, backfill)
+  --set KEY=VALUE      override a config key, may be repeated (run, check-config, decode, backfill)
+  --abi PATH           contract ABI (decode, mint), defaults to abi/AuthToken.json
+  --from N, --to M     block range (backfill)
+  --rpc-uri URI        node to send the transaction to (mint), defaults to http://localhost:8545
+  --contract ADDRESS   token contract (mint)
+  --account ADDRESS    unlocked node account sending the transaction (mint)
+  --first-token-id N   id of the first minted token (mint), defaults to 0";
+
+const DEFAULT_ABI: &str = "abi/AuthToken.json";
+
+fn main() {
     let _ = env_logger::try_init();
-    let http = web3::transports::Http::new("http://localhost:8545")?;
-    let web3 = web3::Web3::new(http);
-    let contract_addr: Address = hex!("465a4A8DAA955B837957230385AC4A9997aa9d27").into();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let (command, flags) = match args.split_first() {
+        Some((command, rest)) => (command.as_str(), Flags::parse(rest)),
+        None => {
+            eprintln!("{}", USAGE);
+            std::process::exit(2);
+        }
+    };
+    let result = flags.and_then(|flags| match command {
+        "run" => {
+            let path = find_path(flags.get("config"))?;
+            producer::run(&path, || {
+                PipelineConfig::from_config(&read_config(&flags, &path)?)
+            })
+        }
+        "check-config" => check_config(&flags),
+        "decode" => decode(&flags),
+        "backfill" => {
+            let (from, to) = (flags.required("from")?, flags.required("to")?);
+            producer::backfill(&load_config(&flags)?.1, from, to)
+        }
+        "mint" => new_runtime()?.block_on(mint(&flags)),
+        "help" | "--help" | "-h" => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        other => Err(Error::invalid_param(&format!(
+            "unknown command {}\n\n{}",
+            other, USAGE
+        ))),
+    });
+    if let Err(err) = result {
+        eprintln!("{}", err.chain_msg());
+        std::process::exit(1);
+    }
+}
+
+/// 子命令之后的 `--name value` 选项，`--set` 可以重复
+#[derive(Debug)]
+struct Flags(Vec<(String, String)>);
+
+impl Flags {
+    fn parse(args: &[String]) -> Result<Self> {
+        let mut flags = vec![];
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let name = arg
+                .strip_prefix("--")
+                .ok_or_else(|| Error::invalid_param(&format!("unexpected argument {}", arg)))?;
+            let value = args
+                .next()
+                .ok_or_else(|| Error::invalid_param(&format!("--{} expects a value", name)))?;
+            flags.push((name.to_owned(), value.clone()));
+        }
+        Ok(Self(flags))
+    }
+
+    /// 最后一次出现的值
+    fn get(&self, name: &str) -> Option<&str> {
+        self.0
+            .iter()
+            .rev()
+            .find(|(flag, _)| flag == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn all(&self, name: &str) -> Vec<&str> {
+        self.0
+            .iter()
+            .filter(|(flag, _)| flag == name)
+            .map(|(_, value)| value.as_str())
+            .collect()
+    }
+
+    fn optional<T: FromStr>(&self, name: &str) -> Result<Option<T>>
+    where
+        T::Err: std::fmt::Display,
+    {
+        self.get(name)
+            .map(|value| {
+                value.parse().map_err(|err| {
+                    Error::invalid_param(&format!("invalid --{} {} - {}", name, value, err))
+                })
+            })
+            .transpose()
+    }
+
+    fn required<T: FromStr>(&self, name: &str) -> Result<T>
+    where
+        T::Err: std::fmt::Display,
+    {
+        self.optional(name)?
+            .ok_or_else(|| Error::invalid_param(&format!("missing --{}", name)))
+    }
+}
+
+/// 按 `--config` 找到配置文件并读取
+fn load_config(flags: &Flags) -> Result<(PathBuf, LayeredConfig<FileConfig>)> {
+    let path = find_path(flags.get("config"))?;
+    let config = read_config(flags, &path)?;
+    Ok((path, config))
+}
+
+/// 读取配置文件，依次叠加环境变量与 `--set`，`run` 在文件修改后重新读取
+fn read_config(flags: &Flags, path: &Path) -> Result<LayeredConfig<FileConfig>> {
+    LayeredConfig::new(FileConfig::from_path(path)?)
+        .with_env()
+        .with_sets(flags.all("set"))
+}
 
-    let my_account: Address = hex!("72d67E96950B7E66AF81AFE1C32307128658d98e").into();
+/// 只校验配置文件，不启动管道
+fn check_config(flags: &Flags) -> Result<()> {
+    let (path, config) = load_config(flags)?;
+    PipelineConfig::from_config(&config)?;
+    println!("{} is valid", path.display());
+    Ok(())
+}
+
+/// 解码标准输入中的一条日志或一笔交易，以 JSON 输出事件
+///
+/// 带有 `topics` 的 JSON 是日志，使用配置中的解码器；带有 `input` 的是交易，按 `--abi` 解码调用数据。
+/// 没有匹配的事件或函数时返回 `not_found` 错误。
+fn decode(flags: &Flags) -> Result<()> {
+    let mut input = String::new();
+    std::io::stdin().read_to_string(&mut input)?;
+    let json: serde_json::Value = serde_json::from_str(&input)
+        .map_err(|err| Error::invalid_data(&format!("invalid JSON - {}", err)))?;
+
+    let event = if json.get("topics").is_some() {
+        let log: Log = serde_json::from_value(json)
+            .map_err(|err| Error::invalid_data(&format!("invalid log - {}", err)))?;
+        let config = PipelineConfig::from_config(&load_config(flags)?.1)?;
+        config.decoder.build()?.decode(&log)?
+    } else if let Some(calldata) = json.get("input") {
+        let calldata: Bytes = serde_json::from_value(calldata.clone())
+            .map_err(|err| Error::invalid_data(&format!("invalid transaction input - {}", err)))?;
+        let abi = flags.get("abi").unwrap_or(DEFAULT_ABI);
+        CallDecoder::from_abi_path(abi)?.decode(&calldata.0)?
+    } else {
+        return Err(Error::invalid_data(
+            "expects a log with topics or a transaction with input",
+        ));
+    };
+    let event = event.ok_or_else(|| Error::not_found("no matching event or function"))?;
+    let json = serde_json::to_string_pretty(&event_json(&event))
+        .map_err(|err| Error::internal(&err.to_string()))?;
+    println!("{}", json);
+    Ok(())
+}
+
+fn event_json(event: &Event) -> serde_json::Value {
+    event
+        .iter()
+        .map(|(name, value)| (name.clone(), value_json(value)))
+        .collect::<serde_json::Map<_, _>>()
+        .into()
+}
 
-    let contract = Contract::from_json(
-        web3.eth(),
-        contract_addr,
-        include_bytes!("../abi/AuthToken.json"),
-    )?;
+/// 字节数组输出为 `0x` 开头的十六进制，无法表示的浮点数输出为 `null`
+fn value_json(value: &Value) -> serde_json::Value {
+    match value {
+        Value::String(val) => val.clone().into(),
+        Value::Integer(val) => (*val).into(),
+        Value::Number(val) => {
+            serde_json::Number::from_f64(*val).map_or(serde_json::Value::Null, Into::into)
+        }
+        Value::Boolean(val) => (*val).into(),
+        Value::Bytes(val) => val
+            .0
+            .iter()
+            .fold("0x".to_owned(), |hex, byte| hex + &format!("{:02x}", byte))
+            .into(),
+        Value::Array(val) => val.iter().map(value_json).collect(),
+        Value::Nil => serde_json::Value::Null,
+    }
+}
+
+/// 用节点上已解锁的 `--account` 调用 `batchMintWithURI`，给节点的每个账户铸造一个 token
+async fn mint(flags: &Flags) -> Result<()> {
+    let rpc_uri = flags.get("rpc-uri").unwrap_or("http://localhost:8545");
+    let contract_addr: Address = flags.required("contract")?;
+    let account: Address = flags.required("account")?;
+    let first_token_id: u64 = flags.optional("first-token-id")?.unwrap_or(0);
+    let abi = std::fs::read(flags.get("abi").unwrap_or(DEFAULT_ABI))?;
+
+    let web3 = web3::Web3::new(web3::transports::Http::new(rpc_uri)?);
+    let contract = Contract::from_json(web3.eth(), contract_addr, &abi)?;
+    let reverts = RevertDecoder::from_abi(&abi)?;
 
     let mut addrs: Vec<Token> = vec![];
     let mut tokens: Vec<Token> = vec![];
     let mut uris: Vec<Token> = vec![];
-
-    let mut token_id: usize = 0;
-    for account in web3.eth().accounts().await? {
-        let bytes = account.as_bytes();
-        addrs.push(Token::Address(Address::from_slice(bytes)));
+    for (token_id, holder) in (first_token_id..).zip(web3.eth().accounts().await?) {
+        addrs.push(Token::Address(holder));
         tokens.push(Token::Uint(token_id.into()));
         uris.push(Token::String(token_id.to_string()));
-        token_id += 1;
     }
 
     let params = vec![
@@ -40,26 +238,25 @@ async fn main() -> web3::contract::Result<()> {
         Token::Array(tokens.clone()),
         Token::Array(uris.clone()),
     ];
-
     let bytes = contract
         .abi()
         .function("batchMintWithURI")?
         .encode_input(&params)?;
 
     let gas_price = web3.eth().gas_price().await?;
-
     let gaslimit = web3
         .eth()
         .estimate_gas(
             CallRequest::builder()
                 .gas_price(gas_price)
                 .data(Bytes::from(bytes))
-                .from(my_account)
+                .from(account)
                 .to(contract_addr)
                 .build(),
             None,
         )
-        .await?;
+        .await
+        .map_err(|err| reverts.resolve(err.into()))?;
 
     let options = Options::with(move |a| {
         a.gas = Some(gaslimit);
@@ -67,16 +264,74 @@ async fn main() -> web3::contract::Result<()> {
     });
 
     println!("send call: batchMintWithURI");
-
     let tx = contract
-        .call(
-            "batchMintWithURI",
-            (addrs, tokens, uris),
-            my_account,
-            options,
-        )
-        .await?;
+        .call("batchMintWithURI", (addrs, tokens, uris), account, options)
+        .await
+        .map_err(|err| reverts.resolve(err.into()))?;
 
     println!("got tx: {:?}", tx);
-    return Ok(());
+    Ok(())
+}
+
+fn new_runtime() -> Result<tokio::runtime::Runtime> {
+    Ok(tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?)
+}
+
+#[cfg(test)]
+fn args(args: &[&str]) -> Vec<String> {
+    args.iter().map(|arg| arg.to_string()).collect()
+}
+
+#[test]
+fn test_flags() {
+    let flags = Flags::parse(&args(&[
+        "--from", "5", "--set", "a=1", "--to", "9", "--set", "b=2", "--from", "6",
+    ]))
+    .unwrap();
+    assert_eq!(Some("6"), flags.get("from"));
+    assert_eq!(vec!["a=1", "b=2"], flags.all("set"));
+    assert_eq!(9_u64, flags.required::<u64>("to").unwrap());
+    assert_eq!(None, flags.optional::<u64>("first-token-id").unwrap());
+    assert_eq!(
+        "missing --contract",
+        flags.required::<u64>("contract").unwrap_err().get_msg()
+    );
+    assert_eq!(
+        "invalid --set b=2 - invalid digit found in string",
+        flags.required::<u64>("set").unwrap_err().get_msg()
+    );
+    assert!(Flags::parse(&[]).unwrap().0.is_empty());
+
+    let err = Flags::parse(&args(&["--from", "5", "9"])).unwrap_err();
+    assert!(err.is_invalid_err());
+    assert_eq!("unexpected argument 9", err.get_msg());
+    let err = Flags::parse(&args(&["--from"])).unwrap_err();
+    assert_eq!("--from expects a value", err.get_msg());
+}
+
+#[test]
+fn test_event_json() {
+    let mut event = Event::new();
+    event.insert("from".to_owned(), Value::from("0x0a"));
+    event.insert("value".to_owned(), Value::Integer(10));
+    event.insert("ratio".to_owned(), Value::Number(0.5));
+    let data = Value::from("0x01ab")
+        .cast(producer::DataType::Bytes)
+        .unwrap();
+    event.insert("data".to_owned(), data);
+    event.insert("ids".to_owned(), Value::from(vec![1, 2]));
+    event.insert("memo".to_owned(), Value::Nil);
+    assert_eq!(
+        serde_json::json!({
+            "from": "0x0a",
+            "value": 10,
+            "ratio": 0.5,
+            "data": "0x01ab",
+            "ids": [1, 2],
+            "memo": null,
+        }),
+        event_json(&event)
+    );
 }
@@ -0,0 +1,86 @@
+use std::collections::BTreeMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
+use tokio::time::sleep;
+
+/// 进程内的指标，按 Prometheus 文本格式输出
+///
+/// 指标名与标签组成一条时间序列，如 `rpc_rate_limit_tokens{endpoint="localhost:8545"}`。
+/// 管道运行时按 `metrics_interval` 定期输出到日志，见 `Metrics::report`。
+pub struct Metrics;
+
+fn registry() -> &'static Mutex<BTreeMap<String, f64>> {
+    static REGISTRY: OnceLock<Mutex<BTreeMap<String, f64>>> = OnceLock::new();
+    REGISTRY.get_or_init(Default::default)
+}
+
+fn series(name: &str, labels: &[(&str, &str)]) -> String {
+    if labels.is_empty() {
+        return name.to_owned();
+    }
+    let labels: Vec<String> = labels
+        .iter()
+        .map(|(key, value)| format!("{}=\"{}\"", key, value.replace('"', "\\\"")))
+        .collect();
+    format!("{}{{{}}}", name, labels.join(","))
+}
+
+impl Metrics {
+    /// 设置当前值
+    pub fn set(name: &str, labels: &[(&str, &str)], value: f64) {
+        registry()
+            .lock()
+            .unwrap()
+            .insert(series(name, labels), value);
+    }
+
+    /// 累加计数
+    pub fn add(name: &str, labels: &[(&str, &str)], value: f64) {
+        *registry()
+            .lock()
+            .unwrap()
+            .entry(series(name, labels))
+            .or_default() += value;
+    }
+
+    pub fn get(name: &str, labels: &[(&str, &str)]) -> Option<f64> {
+        registry()
+            .lock()
+            .unwrap()
+            .get(&series(name, labels))
+            .copied()
+    }
+
+    /// 按 Prometheus 文本格式输出所有指标
+    pub fn render() -> String {
+        registry()
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(series, value)| format!("{} {}\n", series, value))
+            .collect()
+    }
+
+    /// 每隔 `interval` 按 info 级别输出一次所有指标，没有指标时不输出
+    pub async fn report(interval: Duration) {
+        loop {
+            sleep(interval).await;
+            let text = Metrics::render();
+            if !text.is_empty() {
+                log::info!("metrics:\n{}", text.trim_end());
+            }
+        }
+    }
+}
+
+#[test]
+fn test_metrics() {
+    Metrics::set("test_gauge", &[("endpoint", "a\"b")], 1.5);
+    Metrics::add("test_counter", &[], 1.0);
+    Metrics::add("test_counter", &[], 2.0);
+    assert_eq!(Some(3.0), Metrics::get("test_counter", &[]));
+    let text = Metrics::render();
+    assert!(text.contains("test_gauge{endpoint=\"a\\\"b\"} 1.5\n"));
+    assert!(text.contains("test_counter 3\n"));
+}
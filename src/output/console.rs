@@ -1,18 +1,29 @@
-use tokio::{runtime::Builder, sync::mpsc::Receiver};
+use async_trait::async_trait;
 
-use crate::{event::Event, new_runtime, Config, Result};
+use crate::{config::Section, event::Event, Config, Result};
 
 use super::Output;
+
+/// `type = "console"` 的配置
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConsoleOutputConfig {
+    pub max_thread: usize,
+}
+
+impl ConsoleOutputConfig {
+    pub fn from_section<C: Config>(section: &mut Section<C>) -> Self {
+        Self {
+            max_thread: section.get_or("max_thread", 1_usize),
+        }
+    }
+}
+
 pub struct ConsoleOutput {}
 
+#[async_trait]
 impl Output for ConsoleOutput {
-    fn start<C: Config>(config: &C, reciver: Receiver<Event>) -> Result<()> {
-        new_runtime(config, "output.max_thread", async {
-            let mut reciver = reciver;
-            while let Some(event) = reciver.recv().await {
-                println!("{:?}", event);
-            }
-        })?;
-        return Ok(());
+    async fn write(&mut self, event: Event) -> Result<()> {
+        println!("{:?}", event);
+        Ok(())
     }
 }
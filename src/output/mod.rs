@@ -1,10 +1,55 @@
 pub mod console;
 pub mod current_file;
 
-use tokio::sync::mpsc::Receiver;
+use async_trait::async_trait;
 
-use crate::{event::Event, Config, Result};
+use crate::{config::Section, event::Event, Config, Result, RetryPolicy};
 
-pub trait Output {
-    fn start<C: Config>(config: &C, reciver: Receiver<Event>) -> Result<()>;
+use self::console::{ConsoleOutput, ConsoleOutputConfig};
+
+/// 事件输出，由管道从缓冲中依次取出事件写入
+#[async_trait]
+pub trait Output: Send {
+    async fn write(&mut self, event: Event) -> Result<()>;
+
+    /// 写入失败时的重试策略，网络输出可按配置覆盖
+    fn retry_policy(&self) -> RetryPolicy {
+        RetryPolicy::default()
+    }
+}
+
+/// `[output]` 与 `[dead_letter]` 的配置，由 `type` 决定输出类型
+#[derive(Debug, Clone, PartialEq)]
+pub enum OutputConfig {
+    Console(ConsoleOutputConfig),
+}
+
+impl OutputConfig {
+    pub fn from_section<C: Config>(section: &mut Section<C>) -> Option<Self> {
+        match section.required::<String>("type")?.as_str() {
+            "console" => Some(OutputConfig::Console(ConsoleOutputConfig::from_section(
+                section,
+            ))),
+            other => {
+                section.error(
+                    "type",
+                    format!("unknown output type {}, expects console", other),
+                );
+                None
+            }
+        }
+    }
+
+    pub fn build(&self) -> Result<Box<dyn Output>> {
+        match self {
+            OutputConfig::Console(_) => Ok(Box::new(ConsoleOutput {})),
+        }
+    }
+
+    /// 输出使用的线程数
+    pub fn max_thread(&self) -> usize {
+        match self {
+            OutputConfig::Console(config) => config.max_thread,
+        }
+    }
 }
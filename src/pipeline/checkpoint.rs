@@ -0,0 +1,67 @@
+use std::path::PathBuf;
+
+use crate::{Context, Error, Result};
+
+/// 保存在文件中的检查点，即下一个需要读取的区块
+#[derive(Debug, Clone, PartialEq)]
+pub struct Checkpoint {
+    path: Option<PathBuf>,
+}
+
+impl Checkpoint {
+    /// `path` 为 `None` 时检查点只保存在内存中
+    pub fn new(path: Option<&str>) -> Self {
+        Self {
+            path: path.map(PathBuf::from),
+        }
+    }
+
+    /// 读取检查点，文件不存在时返回 `None`
+    pub fn load(&self) -> Result<Option<u64>> {
+        let path = match &self.path {
+            Some(path) if path.exists() => path,
+            _ => return Ok(None),
+        };
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("while reading checkpoint file {}", path.display()))?;
+        content.trim().parse().map(Some).map_err(|err| {
+            Error::invalid_data(&format!(
+                "invalid checkpoint file {} - {}",
+                path.display(),
+                err
+            ))
+        })
+    }
+
+    /// 先写入临时文件再重命名，避免进程退出时留下不完整的检查点
+    pub fn save(&self, checkpoint: u64) -> Result<()> {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        let tmp = path.with_extension("tmp");
+        std::fs::write(&tmp, checkpoint.to_string())
+            .and_then(|_| std::fs::rename(&tmp, path))
+            .with_context(|| format!("while saving checkpoint file {}", path.display()))
+    }
+}
+
+#[test]
+fn test_checkpoint() {
+    let dir = std::env::temp_dir().join("producer_test_checkpoint");
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("checkpoint");
+    let _ = std::fs::remove_file(&path);
+
+    let checkpoint = Checkpoint::new(path.to_str());
+    assert_eq!(None, checkpoint.load().unwrap());
+    checkpoint.save(1024).unwrap();
+    assert_eq!(Some(1024), checkpoint.load().unwrap());
+
+    std::fs::write(&path, "block 10").unwrap();
+    assert!(checkpoint.load().is_err());
+
+    let memory = Checkpoint::new(None);
+    memory.save(10).unwrap();
+    assert_eq!(None, memory.load().unwrap());
+}
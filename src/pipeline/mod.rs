@@ -0,0 +1,298 @@
+mod checkpoint;
+mod reload;
+mod runtime;
+
+pub use self::reload::{watch, WATCH_INTERVAL};
+pub use self::runtime::Pipeline;
+
+use crate::config::{ConfigErrors, Section};
+use crate::decode::DecoderConfig;
+use crate::input::InputConfig;
+use crate::output::OutputConfig;
+use crate::process::validate::ValidateMode;
+use crate::process::ProcessorConfig;
+use crate::{Config, Result};
+
+/// 整个管道的配置，启动前一次性读取并校验所有配置项
+#[derive(Debug, Clone, PartialEq)]
+pub struct PipelineConfig {
+    /// 输入与输出之间的缓冲事件数
+    pub buffer_size: usize,
+    /// 输出指标到日志的间隔（秒），为 0 时不输出
+    pub metrics_interval: u64,
+    pub input: InputConfig,
+    pub decoder: DecoderConfig,
+    pub processors: Vec<ProcessorConfig>,
+    pub output: OutputConfig,
+    /// 死信输出，`processor.validate.mode = "dead_letter"` 时必须配置
+    pub dead_letter: Option<OutputConfig>,
+}
+
+impl PipelineConfig {
+    /// 读取并校验配置，返回的错误中列出所有问题
+    pub fn from_config<C: Config>(config: &C) -> Result<Self> {
+        let mut errors = ConfigErrors::new();
+        let mut root = Section::new(config, "", &mut errors);
+        let pipeline = Self::from_section(&mut root);
+        root.finish();
+        errors.into_result(pipeline)
+    }
+
+    fn from_section<C: Config>(root: &mut Section<C>) -> Option<Self> {
+        let buffer_size = root.get_or("buffer_size", 1024_usize);
+        if buffer_size == 0 {
+            root.error("buffer_size", "must be greater than 0");
+        }
+        let metrics_interval = root.get_or("metrics_interval", 60_u64);
+        let input = required_section(root, "input", InputConfig::from_section);
+        let decoder = required_section(root, "decoder", DecoderConfig::from_section);
+        let processors = {
+            let mut section = root.section("processor");
+            let processors = ProcessorConfig::from_section(&mut section);
+            section.finish();
+            processors
+        };
+        let output = required_section(root, "output", OutputConfig::from_section);
+        let dead_letter = {
+            let mut section = root.section("dead_letter");
+            let dead_letter = if section.exists() {
+                OutputConfig::from_section(&mut section).map(Some)
+            } else {
+                Some(None)
+            };
+            section.finish();
+            dead_letter
+        };
+
+        let processors = processors?;
+        let dead_letter = dead_letter?;
+        let needs_dead_letter = processors.iter().any(|processor| {
+            matches!(processor, ProcessorConfig::Validate(validate) if validate.mode == ValidateMode::DeadLetter)
+        });
+        if needs_dead_letter && dead_letter.is_none() {
+            root.error(
+                "dead_letter",
+                "missing section, required by processor.validate.mode = \"dead_letter\"",
+            );
+        }
+
+        Some(Self {
+            buffer_size,
+            metrics_interval,
+            input: input?,
+            decoder: decoder?,
+            processors,
+            output: output?,
+            dead_letter,
+        })
+    }
+}
+
+fn required_section<C: Config, T>(
+    root: &mut Section<C>,
+    name: &str,
+    read: fn(&mut Section<C>) -> Option<T>,
+) -> Option<T> {
+    let mut section = root.section(name);
+    if !section.exists() {
+        section.error("", "missing section");
+        return None;
+    }
+    let value = read(&mut section);
+    section.finish();
+    value
+}
+
+#[cfg(test)]
+const PIPELINE: &str = r#"
+buffer_size = 16
+
+[input]
+type = "web3_event"
+rpc_uri = "http://localhost:8545"
+contracts = ["0x465a4A8DAA955B837957230385AC4A9997aa9d27"]
+abi = "abi/AuthToken.json"
+events = ["Transfer"]
+confirmations = 2
+
+[decoder]
+type = "transfer"
+
+[processor.validate]
+mode = "dead_letter"
+abi = "abi/AuthToken.json"
+event = "Transfer"
+
+[output]
+type = "console"
+
+[dead_letter]
+type = "console"
+"#;
+
+#[test]
+fn test_pipeline_config() {
+    let config = crate::TomlConfig::from_string(PIPELINE).unwrap();
+    let pipeline = PipelineConfig::from_config(&config).unwrap();
+    assert_eq!(16, pipeline.buffer_size);
+    assert_eq!(60, pipeline.metrics_interval);
+    let InputConfig::Web3Event(input) = &pipeline.input else {
+        panic!("expects web3_event input");
+    };
+    assert_eq!(1, input.contracts.len());
+    assert_eq!(2, input.confirmations);
+    assert_eq!(1000, input.batch_size);
+    assert_eq!((1, 1000), (input.min_batch_size, input.max_batch_size));
+    assert_eq!(1, input.rpc_uris.len());
+
+    let toml = PIPELINE.replace(
+        "rpc_uri = \"http://localhost:8545\"",
+        "rpc_uri = [\"http://localhost:8545\", \"http://localhost:8546\"]",
+    );
+    let config = crate::TomlConfig::from_string(&toml).unwrap();
+    let pipeline = PipelineConfig::from_config(&config).unwrap();
+    let InputConfig::Web3Event(input) = &pipeline.input else {
+        panic!("expects web3_event input");
+    };
+    assert_eq!("http://localhost:8546", input.rpc_uris[1].expose());
+    assert_eq!(None, input.rate_limit);
+
+    let toml = PIPELINE.replace(
+        "[decoder]",
+        "[input.rate_limit]\nrps = 10\nweights = { eth_getLogs = 5 }\n\n[decoder]",
+    );
+    let config = crate::TomlConfig::from_string(&toml).unwrap();
+    let pipeline = PipelineConfig::from_config(&config).unwrap();
+    let InputConfig::Web3Event(input) = &pipeline.input else {
+        panic!("expects web3_event input");
+    };
+    let rate_limit = input.rate_limit.as_ref().unwrap();
+    assert_eq!((10.0, 10.0), (rate_limit.rps, rate_limit.burst));
+    assert_eq!(Some(&5.0), rate_limit.weights.get("eth_getLogs"));
+    assert_eq!(1, pipeline.processors.len());
+    assert!(pipeline.dead_letter.is_some());
+
+    let toml = PIPELINE.replace("buffer_size = 16", "metrics_interval = 0");
+    let config = crate::TomlConfig::from_string(&toml).unwrap();
+    assert_eq!(
+        0,
+        PipelineConfig::from_config(&config)
+            .unwrap()
+            .metrics_interval
+    );
+}
+
+#[test]
+fn test_pipeline_config_errors() {
+    let toml = PIPELINE
+        .replace("confirmations = 2", "confirmations = \"2\"\nrpc_url = \"\"")
+        .replace("0x465a4A8DAA955B837957230385AC4A9997aa9d27", "0x465a")
+        .replace("events = [\"Transfer\"]", "events = [\"Mint\"]")
+        .replace("[dead_letter]\ntype = \"console\"", "")
+        .replace("type = \"transfer\"", "type = \"erc20\"")
+        .replace(
+            "[output]\ntype = \"console\"",
+            "[output]\ntype = \"console\"\nmax_thread = -1",
+        );
+    let config = crate::TomlConfig::from_string(&toml).unwrap();
+    let err = PipelineConfig::from_config(&config).unwrap_err();
+    assert_eq!(
+        "found 7 problem(s) in config:\n  \
+         input.contracts: invalid address 0x465a - Invalid input length\n  \
+         input.abi: Invalid name: Mint\n  \
+         input.confirmations: expects Integer but got String\n  \
+         input.rpc_url: unknown key\n  \
+         decoder.type: unknown decoder type erc20, expects transfer\n  \
+         output.max_thread: -1 is out of range for usize\n  \
+         dead_letter: missing section, required by processor.validate.mode = \"dead_letter\"",
+        err.get_msg()
+    );
+}
+
+#[test]
+fn test_web3_rpc_config() {
+    let input = |input: &str| {
+        let toml = PIPELINE.replace(
+            "type = \"web3_event\"\nrpc_uri = \"http://localhost:8545\"\n\
+             contracts = [\"0x465a4A8DAA955B837957230385AC4A9997aa9d27\"]\n\
+             abi = \"abi/AuthToken.json\"\nevents = [\"Transfer\"]\n",
+            input,
+        );
+        PipelineConfig::from_config(&crate::TomlConfig::from_string(&toml).unwrap())
+    };
+
+    let pipeline = input(
+        "type = \"web3_rpc\"\nrpc_uri = \"http://localhost:8545\"\ntransactions = true\n\
+         to = [\"0x465a4A8DAA955B837957230385AC4A9997aa9d27\"]\nabi = \"abi/AuthToken.json\"\n",
+    )
+    .unwrap();
+    let InputConfig::Web3Rpc(rpc) = &pipeline.input else {
+        panic!("expects web3_rpc input");
+    };
+    assert_eq!((2, 10), (rpc.confirmations, rpc.batch_size));
+    assert_eq!(1, rpc.to.len());
+    assert!(rpc.from.is_empty());
+    assert_eq!(None, rpc.to_block);
+
+    let mut backfill = pipeline.input.clone();
+    assert!(backfill.set_range(10, 9).is_err());
+    backfill.set_range(5, 9).unwrap();
+    let InputConfig::Web3Rpc(range) = &backfill else {
+        panic!("expects web3_rpc input");
+    };
+    assert_eq!(
+        (5, Some(9), None),
+        (
+            range.from_block,
+            range.to_block,
+            range.checkpoint.as_deref()
+        )
+    );
+
+    let err = input(
+        "type = \"web3_rpc\"\nrpc_uri = \"http://localhost:8545\"\nbatch_size = 0\n\
+         from_block = 5\nto_block = 3\n\
+         from = [\"0x465a4A8DAA955B837957230385AC4A9997aa9d27\"]\n",
+    )
+    .unwrap_err();
+    assert_eq!(
+        "found 3 problem(s) in config:\n  \
+         input.to_block: must not be less than from_block\n  \
+         input.batch_size: must be greater than 0\n  \
+         input.from: requires input.transactions = true",
+        err.get_msg()
+    );
+}
+
+#[test]
+fn test_enrich_config() {
+    use crate::process::ProcessorConfig;
+
+    let toml = PIPELINE.replace(
+        "[processor.validate]",
+        "[processor.enrich]\nrpc_uri = \"http://localhost:8545\"\ncache_size = 100\n\n[processor.validate]",
+    );
+    let config = crate::TomlConfig::from_string(&toml).unwrap();
+    let pipeline = PipelineConfig::from_config(&config).unwrap();
+    let ProcessorConfig::Enrich(enrich) = &pipeline.processors[0] else {
+        panic!("expects enrich processor first");
+    };
+    assert_eq!(100, enrich.cache_size);
+    assert!(matches!(
+        pipeline.processors[1],
+        ProcessorConfig::Validate(_)
+    ));
+
+    let toml = PIPELINE.replace(
+        "[processor.validate]",
+        "[processor.enrich]\ncache_size = 0\n\n[processor.validate]",
+    );
+    let config = crate::TomlConfig::from_string(&toml).unwrap();
+    let err = PipelineConfig::from_config(&config).unwrap_err();
+    assert_eq!(
+        "found 2 problem(s) in config:\n  \
+         processor.enrich.rpc_uri: missing required key\n  \
+         processor.enrich.cache_size: must be greater than 0",
+        err.get_msg()
+    );
+}
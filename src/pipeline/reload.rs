@@ -0,0 +1,105 @@
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+use tokio::time::sleep;
+
+use super::{Pipeline, PipelineConfig};
+use crate::Result;
+
+/// 检查配置文件是否修改的间隔
+pub const WATCH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// 监视配置文件，修改后通过 `load` 重新读取配置并重建变化的阶段
+///
+/// 新配置无效或创建阶段失败时记录错误，原有的管道继续运行。
+/// 只检查 `path` 本身的修改时间，`include` 引用的文件修改后需要同时修改主配置文件。
+pub async fn watch<P, F>(pipeline: &mut Pipeline, path: P, interval: Duration, load: F)
+where
+    P: AsRef<Path>,
+    F: Fn() -> Result<PipelineConfig>,
+{
+    let path = path.as_ref();
+    let mut modified = modified_time(path);
+    loop {
+        sleep(interval).await;
+        let current = modified_time(path);
+        if current == modified {
+            continue;
+        }
+        modified = current;
+
+        let config = match load() {
+            Ok(config) => config,
+            Err(err) => {
+                log::error!("reject new config {} - {}", path.display(), err.get_msg());
+                continue;
+            }
+        };
+        match pipeline.reload(config).await {
+            Ok(stages) if stages.is_empty() => {
+                log::info!("config {} reloaded, nothing changed", path.display())
+            }
+            Ok(stages) => log::info!(
+                "config {} reloaded, rebuilt {}",
+                path.display(),
+                stages.join(", ")
+            ),
+            Err(err) => log::error!(
+                "reject new config {}, keep running the old one - {}",
+                path.display(),
+                err.get_msg()
+            ),
+        }
+    }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|meta| meta.modified())
+        .ok()
+}
+
+#[tokio::test]
+async fn test_watch() {
+    use crate::input::InputConfig;
+    use tokio::time::timeout;
+
+    let dir = std::env::temp_dir().join("producer_test_watch");
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("producer.toml");
+    let toml = super::PIPELINE.replace(
+        "rpc_uri = \"http://localhost:8545\"",
+        "rpc_uri = \"http://127.0.0.1:1\"\npoll_interval = 10",
+    );
+    std::fs::write(&path, &toml).unwrap();
+    let load = || PipelineConfig::from_config(&crate::TomlConfig::from_path(&path)?);
+    let confirmations = |pipeline: &Pipeline| match &pipeline.config().input {
+        InputConfig::Web3Event(input) => input.confirmations,
+        _ => panic!("expects web3_event input"),
+    };
+    // 开始监视之后再修改文件，监视一段时间后返回
+    let rewrite = |text: String| {
+        let path = path.clone();
+        tokio::spawn(async move {
+            sleep(Duration::from_millis(50)).await;
+            std::fs::write(path, text).unwrap();
+        })
+    };
+
+    let mut pipeline = Pipeline::start(load().unwrap()).unwrap();
+    rewrite(toml.replace("confirmations = 2", "confirmations = 3"));
+    let interval = Duration::from_millis(10);
+    let watching = watch(&mut pipeline, &path, interval, load);
+    assert!(timeout(Duration::from_millis(500), watching).await.is_err());
+    assert_eq!(3, confirmations(&pipeline));
+    assert_eq!(&load().unwrap(), pipeline.config());
+
+    // 无效的配置被拒绝，原有的管道继续运行
+    let reloaded = pipeline.config().clone();
+    rewrite(toml.replace("confirmations = 2", "confirmations = \"3\""));
+    let watching = watch(&mut pipeline, &path, interval, load);
+    assert!(timeout(Duration::from_millis(500), watching).await.is_err());
+    assert!(load().is_err());
+    assert_eq!(&reloaded, pipeline.config());
+    pipeline.stop().await.unwrap();
+}
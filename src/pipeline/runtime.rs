@@ -0,0 +1,634 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::sync::mpsc::{channel, Receiver, Sender};
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tokio::time::sleep;
+
+use super::checkpoint::Checkpoint;
+use super::PipelineConfig;
+use crate::decode::Decoder;
+use crate::input::{Input, Record};
+use crate::metrics::Metrics;
+use crate::output::Output;
+use crate::process::Processor;
+use crate::{event::Event, Error, Result};
+
+/// 解码与处理阶段，重新加载时整体替换，输入在每一批数据开始时取用最新的版本
+pub struct Transform {
+    decoder: Box<dyn Decoder>,
+    processors: Vec<Box<dyn Processor>>,
+}
+
+impl Transform {
+    pub fn build(config: &PipelineConfig, dead_letter: &Sender<Event>) -> Result<Self> {
+        let dead_letter = config.dead_letter.as_ref().map(|_| dead_letter.clone());
+        Ok(Self {
+            decoder: config.decoder.build()?,
+            processors: config
+                .processors
+                .iter()
+                .map(|processor| processor.build(dead_letter.clone()))
+                .collect::<Result<Vec<Box<dyn Processor>>>>()?,
+        })
+    }
+
+    /// 解码并处理一批数据，无法解码的日志记录后丢弃
+    pub async fn apply(&self, records: Vec<Record>) -> Result<Vec<Event>> {
+        let mut events = Vec::with_capacity(records.len());
+        for record in records {
+            match record {
+                Record::Event(event) => events.push(event),
+                Record::Log(log) => match self.decoder.decode(&log) {
+                    Ok(Some(event)) => events.push(event),
+                    Ok(None) => {}
+                    Err(err) => log::warn!("drop undecodable log {:?} - {}", log, err),
+                },
+            }
+        }
+        for processor in &self.processors {
+            events = processor.process(events).await?;
+        }
+        Ok(events)
+    }
+}
+
+/// 运行中的一个阶段，停止后返回阶段持有的状态
+struct Stage<T> {
+    stop: watch::Sender<bool>,
+    /// 阶段结束时发送端随之释放
+    done: watch::Receiver<()>,
+    handle: JoinHandle<T>,
+}
+
+impl<T: Send + 'static> Stage<T> {
+    fn spawn<F, Fut>(run: F) -> Self
+    where
+        F: FnOnce(watch::Receiver<bool>) -> Fut,
+        Fut: std::future::Future<Output = T> + Send + 'static,
+    {
+        let (stop, stopped) = watch::channel(false);
+        let (running, done) = watch::channel(());
+        let run = run(stopped);
+        Self {
+            stop,
+            done,
+            handle: tokio::spawn(async move {
+                let result = run.await;
+                drop(running);
+                result
+            }),
+        }
+    }
+
+    /// 等待阶段自行结束，不取走阶段的状态
+    async fn finished(&mut self) {
+        while self.done.changed().await.is_ok() {}
+    }
+
+    /// 通知阶段停止并等待其结束，之后需要替换为新的阶段
+    async fn stop(&mut self) -> Result<T> {
+        let _ = self.stop.send(true);
+        self.join().await
+    }
+
+    async fn join(&mut self) -> Result<T> {
+        (&mut self.handle)
+            .await
+            .map_err(|err| Error::internal(&format!("pipeline stage panicked - {}", err)))
+    }
+}
+
+/// 运行中的管道
+///
+/// 输入、处理、输出与死信输出分别运行，重新加载时只重建配置发生变化的阶段：
+/// 输入停止后从内存中的检查点继续读取，输出停止后将缓冲中的事件交给新的输出。
+pub struct Pipeline {
+    config: PipelineConfig,
+    sender: Sender<Event>,
+    dead_letter_sender: Sender<Event>,
+    transform: watch::Sender<Arc<Transform>>,
+    input: Stage<u64>,
+    output: Stage<Receiver<Event>>,
+    dead_letter: Option<Stage<Receiver<Event>>>,
+    /// 没有配置死信输出时保留缓冲，等待之后配置
+    dead_letter_buffer: Option<Receiver<Event>>,
+    /// 定期输出指标到日志
+    metrics: Option<JoinHandle<()>>,
+}
+
+impl Pipeline {
+    /// 在当前的 tokio 运行时中启动所有阶段
+    pub fn start(config: PipelineConfig) -> Result<Self> {
+        let (sender, reciver) = channel(config.buffer_size);
+        let (dead_letter_sender, dead_letter_reciver) = channel(config.buffer_size);
+
+        let transform = Transform::build(&config, &dead_letter_sender)?;
+        let input = config.input.build()?;
+        let output = config.output.build()?;
+        let dead_letter = config
+            .dead_letter
+            .as_ref()
+            .map(|dead_letter| dead_letter.build())
+            .transpose()?;
+
+        let checkpoint = Checkpoint::new(config.input.checkpoint());
+        let position = checkpoint.load()?;
+        let (transform, transforms) = watch::channel(Arc::new(transform));
+        let (dead_letter, dead_letter_buffer) = match dead_letter {
+            Some(output) => (Some(spawn_output(output, dead_letter_reciver)), None),
+            None => (None, Some(dead_letter_reciver)),
+        };
+        Ok(Self {
+            input: spawn_input(input, position, checkpoint, transforms, sender.clone()),
+            output: spawn_output(output, reciver),
+            dead_letter,
+            dead_letter_buffer,
+            metrics: spawn_metrics(config.metrics_interval),
+            config,
+            sender,
+            dead_letter_sender,
+            transform,
+        })
+    }
+
+    pub fn config(&self) -> &PipelineConfig {
+        &self.config
+    }
+
+    /// 切换到新的配置，返回重建的阶段名
+    ///
+    /// 先创建所有需要替换的阶段，任何一个失败时返回错误，原有的管道继续运行。
+    pub async fn reload(&mut self, config: PipelineConfig) -> Result<Vec<&'static str>> {
+        let old = &self.config;
+        if config.buffer_size != old.buffer_size {
+            log::warn!(
+                "buffer_size can't be changed while running, keep {}",
+                old.buffer_size
+            );
+        }
+        let transform_changed = config.decoder != old.decoder
+            || config.processors != old.processors
+            || config.dead_letter.is_some() != old.dead_letter.is_some();
+        let transform = transform_changed
+            .then(|| Transform::build(&config, &self.dead_letter_sender))
+            .transpose()?;
+        let input = (config.input != old.input)
+            .then(|| config.input.build())
+            .transpose()?;
+        let output = (config.output != old.output)
+            .then(|| config.output.build())
+            .transpose()?;
+        let dead_letter = (config.dead_letter != old.dead_letter)
+            .then(|| {
+                config
+                    .dead_letter
+                    .as_ref()
+                    .map(|dead_letter| dead_letter.build())
+                    .transpose()
+            })
+            .transpose()?;
+
+        let mut stages = vec![];
+        if config.metrics_interval != old.metrics_interval {
+            if let Some(metrics) = self.metrics.take() {
+                metrics.abort();
+            }
+            self.metrics = spawn_metrics(config.metrics_interval);
+            stages.push("metrics");
+        }
+        if let Some(transform) = transform {
+            self.transform.send_replace(Arc::new(transform));
+            stages.push("transform");
+        }
+        if let Some(input) = input {
+            // 继续使用内存中的检查点，新配置中的起始区块与检查点文件不再生效
+            let position = self.input.stop().await?;
+            self.input = spawn_input(
+                input,
+                Some(position),
+                Checkpoint::new(config.input.checkpoint()),
+                self.transform.subscribe(),
+                self.sender.clone(),
+            );
+            stages.push("input");
+        }
+        if let Some(output) = output {
+            let reciver = self.output.stop().await?;
+            self.output = spawn_output(output, reciver);
+            stages.push("output");
+        }
+        if let Some(dead_letter) = dead_letter {
+            let reciver = match self.dead_letter.take() {
+                Some(mut stage) => stage.stop().await?,
+                None => self
+                    .dead_letter_buffer
+                    .take()
+                    .expect("dead letter buffer is kept while no output is configured"),
+            };
+            match dead_letter {
+                Some(output) => self.dead_letter = Some(spawn_output(output, reciver)),
+                None => self.dead_letter_buffer = Some(reciver),
+            }
+            stages.push("dead_letter");
+        }
+
+        let buffer_size = self.config.buffer_size;
+        self.config = config;
+        self.config.buffer_size = buffer_size;
+        Ok(stages)
+    }
+
+    /// 等待输入读取到结束区块，输入没有结束区块时一直等待
+    pub async fn finished(&mut self) {
+        self.input.finished().await
+    }
+
+    /// 停止输入，等待输出写完缓冲中的事件
+    pub async fn stop(mut self) -> Result<()> {
+        if let Some(metrics) = self.metrics.take() {
+            metrics.abort();
+        }
+        let position = self.input.stop().await?;
+        drop(self.transform);
+        drop(self.sender);
+        drop(self.dead_letter_sender);
+        self.output.join().await?;
+        if let Some(mut dead_letter) = self.dead_letter {
+            dead_letter.join().await?;
+        }
+        log::info!("pipeline stopped at checkpoint {}", position);
+        Ok(())
+    }
+}
+
+fn spawn_input(
+    input: Box<dyn Input>,
+    position: Option<u64>,
+    checkpoint: Checkpoint,
+    transforms: watch::Receiver<Arc<Transform>>,
+    sender: Sender<Event>,
+) -> Stage<u64> {
+    Stage::spawn(move |stop| run_input(input, position, checkpoint, transforms, sender, stop))
+}
+
+fn spawn_metrics(interval: u64) -> Option<JoinHandle<()>> {
+    (interval > 0).then(|| tokio::spawn(Metrics::report(Duration::from_secs(interval))))
+}
+
+fn spawn_output(output: Box<dyn Output>, reciver: Receiver<Event>) -> Stage<Receiver<Event>> {
+    Stage::spawn(move |stop| run_output(output, reciver, stop))
+}
+
+/// 循环读取数据，处理后发送到输出，返回停止时的检查点
+///
+/// 停止信号只在读取数据时检查，已经读取的一批数据总会发送完成并记录检查点，
+/// 读取或处理失败时回到检查点重新读取。输入有结束区块时读取完成后返回。
+async fn run_input(
+    mut input: Box<dyn Input>,
+    position: Option<u64>,
+    checkpoint: Checkpoint,
+    transforms: watch::Receiver<Arc<Transform>>,
+    sender: Sender<Event>,
+    mut stop: watch::Receiver<bool>,
+) -> u64 {
+    if let Some(position) = position {
+        input.seek(position);
+    }
+    let mut position = input.position();
+    loop {
+        if let Some(end) = input.end().filter(|end| position > *end) {
+            log::info!("input reached end block {}", end);
+            return position;
+        }
+        let batch = tokio::select! {
+            _ = stop.changed() => return position,
+            batch = input.next() => batch,
+        };
+        let result = match batch {
+            Ok(batch) => {
+                let transform = transforms.borrow().clone();
+                transform
+                    .apply(batch.records)
+                    .await
+                    .map(|events| (events, batch.checkpoint))
+            }
+            Err(err) => Err(err),
+        };
+        let (events, next) = match result {
+            Ok(result) => result,
+            Err(err) => {
+                log::error!("input failed, retry from {} - {}", position, err);
+                input.seek(position);
+                tokio::select! {
+                    _ = stop.changed() => return position,
+                    _ = sleep(Duration::from_millis(input.retry_interval())) => continue,
+                }
+            }
+        };
+        for event in events {
+            if sender.send(event).await.is_err() {
+                return position;
+            }
+        }
+        position = next;
+        if let Err(err) = checkpoint.save(next) {
+            log::error!("failed to save checkpoint {} - {}", next, err);
+        }
+    }
+}
+
+/// 依次写出缓冲中的事件，停止时返回缓冲，未写出的事件留给下一个输出
+async fn run_output(
+    mut output: Box<dyn Output>,
+    mut reciver: Receiver<Event>,
+    mut stop: watch::Receiver<bool>,
+) -> Receiver<Event> {
+    loop {
+        let event = tokio::select! {
+            biased;
+            _ = stop.changed() => break,
+            event = reciver.recv() => event,
+        };
+        match event {
+            Some(event) => write(output.as_mut(), event).await,
+            None => break,
+        }
+    }
+    reciver
+}
+
+/// 按输出的重试策略写入事件，放弃时丢弃事件
+async fn write(output: &mut dyn Output, event: Event) {
+    let mut backoff = output.retry_policy().backoff();
+    loop {
+        let err = match output.write(event.clone()).await {
+            Ok(()) => return,
+            Err(err) => err,
+        };
+        match backoff.next_delay(&err) {
+            Some(delay) => {
+                log::warn!(
+                    "failed to write event, retry in {}ms - {}",
+                    delay.as_millis(),
+                    err
+                );
+                sleep(delay).await;
+            }
+            None => {
+                log::error!("failed to write event - {}", err);
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+struct CountInput {
+    next: u64,
+    limit: u64,
+    end: Option<u64>,
+}
+
+#[cfg(test)]
+#[async_trait::async_trait]
+impl Input for CountInput {
+    fn seek(&mut self, checkpoint: u64) {
+        self.next = checkpoint;
+    }
+
+    fn position(&self) -> u64 {
+        self.next
+    }
+
+    fn end(&self) -> Option<u64> {
+        self.end
+    }
+
+    async fn next(&mut self) -> Result<crate::input::Batch> {
+        if self.next >= self.limit {
+            std::future::pending::<()>().await;
+        }
+        let mut event = Event::new();
+        event.insert("block".to_owned(), crate::Value::from(self.next as i64));
+        self.next += 1;
+        Ok(crate::input::Batch {
+            records: vec![Record::Event(event)],
+            checkpoint: self.next,
+        })
+    }
+}
+
+#[cfg(test)]
+struct RecordOutput {
+    events: Arc<std::sync::Mutex<Vec<Event>>>,
+}
+
+#[cfg(test)]
+#[async_trait::async_trait]
+impl Output for RecordOutput {
+    async fn write(&mut self, event: Event) -> Result<()> {
+        self.events.lock().unwrap().push(event);
+        Ok(())
+    }
+}
+
+/// 前 `failures` 次写入超时
+#[cfg(test)]
+struct FlakyOutput {
+    failures: usize,
+    inner: RecordOutput,
+}
+
+#[cfg(test)]
+#[async_trait::async_trait]
+impl Output for FlakyOutput {
+    async fn write(&mut self, event: Event) -> Result<()> {
+        if self.failures > 0 {
+            self.failures -= 1;
+            return Err(Error::timeout("write timed out"));
+        }
+        self.inner.write(event).await
+    }
+
+    fn retry_policy(&self) -> crate::RetryPolicy {
+        crate::RetryPolicy {
+            initial_interval: 1,
+            max_interval: 1,
+            max_elapsed: 0,
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(test)]
+fn pipeline_config() -> PipelineConfig {
+    let toml = super::PIPELINE.replace(
+        "rpc_uri = \"http://localhost:8545\"",
+        "rpc_uri = \"http://127.0.0.1:1\"\npoll_interval = 10",
+    );
+    PipelineConfig::from_config(&crate::TomlConfig::from_string(&toml).unwrap()).unwrap()
+}
+
+#[tokio::test]
+async fn test_input_resume() {
+    let dir = std::env::temp_dir().join("producer_test_input_resume");
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("checkpoint");
+    let _ = std::fs::remove_file(&path);
+    let checkpoint = Checkpoint::new(path.to_str());
+
+    let mut config = pipeline_config();
+    config.processors.clear();
+    let (dead_letter, _) = channel(1);
+    let transform = Transform::build(&config, &dead_letter).unwrap();
+    let (_transform, transforms) = watch::channel(Arc::new(transform));
+    let (sender, mut reciver) = channel(10);
+
+    let input = Box::new(CountInput {
+        next: 0,
+        limit: 3,
+        end: None,
+    });
+    let mut stage = spawn_input(
+        input,
+        None,
+        checkpoint.clone(),
+        transforms.clone(),
+        sender.clone(),
+    );
+    for _ in 0..3 {
+        reciver.recv().await.unwrap();
+    }
+    let position = stage.stop().await.unwrap();
+    assert_eq!(3, position);
+    assert_eq!(Some(3), checkpoint.load().unwrap());
+
+    let input = Box::new(CountInput {
+        next: 0,
+        limit: 5,
+        end: None,
+    });
+    let mut stage = spawn_input(
+        input,
+        Some(position),
+        checkpoint.clone(),
+        transforms,
+        sender,
+    );
+    let event = reciver.recv().await.unwrap();
+    assert_eq!(Some(&crate::Value::Integer(3)), event.get("block"));
+    reciver.recv().await.unwrap();
+    assert_eq!(5, stage.stop().await.unwrap());
+}
+
+#[tokio::test]
+async fn test_input_end() {
+    let mut config = pipeline_config();
+    config.processors.clear();
+    let (dead_letter, _) = channel(1);
+    let transform = Transform::build(&config, &dead_letter).unwrap();
+    let (_transform, transforms) = watch::channel(Arc::new(transform));
+    let (sender, mut reciver) = channel(10);
+
+    let input = Box::new(CountInput {
+        next: 0,
+        limit: 10,
+        end: Some(2),
+    });
+    let mut stage = spawn_input(input, Some(1), Checkpoint::new(None), transforms, sender);
+    stage.finished().await;
+    assert_eq!(3, stage.stop().await.unwrap());
+    let mut blocks = vec![];
+    while let Some(event) = reciver.recv().await {
+        blocks.push(event["block"].clone());
+    }
+    assert_eq!(
+        vec![crate::Value::Integer(1), crate::Value::Integer(2)],
+        blocks
+    );
+}
+
+#[tokio::test]
+async fn test_output_keeps_buffer() {
+    let (sender, reciver) = channel(10);
+    for block in 0..3 {
+        let mut event = Event::new();
+        event.insert("block".to_owned(), crate::Value::from(block));
+        sender.send(event).await.unwrap();
+    }
+
+    let events = Arc::new(std::sync::Mutex::new(vec![]));
+    let output = Box::new(RecordOutput {
+        events: events.clone(),
+    });
+    let mut stage = spawn_output(output, reciver);
+    let reciver = stage.stop().await.unwrap();
+    assert!(events.lock().unwrap().is_empty());
+
+    let mut stage = spawn_output(
+        Box::new(RecordOutput {
+            events: events.clone(),
+        }),
+        reciver,
+    );
+    drop(sender);
+    stage.join().await.unwrap();
+    assert_eq!(3, events.lock().unwrap().len());
+}
+
+#[tokio::test]
+async fn test_output_retry() {
+    let events = Arc::new(std::sync::Mutex::new(vec![]));
+    let mut output = FlakyOutput {
+        failures: 3,
+        inner: RecordOutput {
+            events: events.clone(),
+        },
+    };
+    write(&mut output, Event::new()).await;
+    assert_eq!(0, output.failures);
+    assert_eq!(1, events.lock().unwrap().len());
+}
+
+#[tokio::test]
+async fn test_reload() {
+    let config = pipeline_config();
+    let mut pipeline = Pipeline::start(config.clone()).unwrap();
+    assert!(pipeline.reload(config.clone()).await.unwrap().is_empty());
+
+    let mut added = config.clone();
+    let crate::input::InputConfig::Web3Event(input) = &mut added.input else {
+        panic!("expects web3_event input");
+    };
+    input
+        .contracts
+        .push(web3::types::Address::from_low_u64_be(1));
+    assert_eq!(vec!["input"], pipeline.reload(added.clone()).await.unwrap());
+
+    let mut removed = added.clone();
+    removed.processors.clear();
+    removed.dead_letter = None;
+    assert_eq!(
+        vec!["transform", "dead_letter"],
+        pipeline.reload(removed.clone()).await.unwrap()
+    );
+
+    removed.metrics_interval = 0;
+    assert_eq!(
+        vec!["metrics"],
+        pipeline.reload(removed.clone()).await.unwrap()
+    );
+    assert!(pipeline.metrics.is_none());
+
+    let mut broken = config;
+    let crate::input::InputConfig::Web3Event(input) = &mut broken.input else {
+        panic!("expects web3_event input");
+    };
+    input.rpc_uris = vec![crate::Secret::new("not a url")];
+    assert!(pipeline.reload(broken).await.is_err());
+    assert_eq!(&removed, pipeline.config());
+
+    pipeline.stop().await.unwrap();
+}
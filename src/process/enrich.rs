@@ -0,0 +1,413 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::hash::Hash;
+use std::str::FromStr;
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+use jsonrpc_core::Value as Json;
+use web3::types::{Address, Block, TransactionReceipt, H256, U256, U64};
+use web3::{helpers, BatchTransport, Transport};
+
+use super::Processor;
+use crate::config::{Secret, Section};
+use crate::input::{rate_limit, retry, rpc_uris};
+use crate::{
+    event::Event, Config, Context, Error, RateLimitConfig, Result, RetryPolicy, RpcPool, Value,
+};
+
+/// `[processor.enrich]` 的配置
+#[derive(Debug, Clone, PartialEq)]
+pub struct EnrichConfig {
+    /// RPC 节点地址，可能带有 API key；`rpc_uri` 可以是一个地址或地址数组
+    pub rpc_uris: Vec<Secret>,
+    /// 缓存的交易回执数与区块数
+    pub cache_size: usize,
+    /// RPC 调用失败时的重试策略，`[processor.enrich.retry]`
+    pub retry: RetryPolicy,
+    /// 每个节点的限流，`[processor.enrich.rate_limit]`，不配置时不限流
+    pub rate_limit: Option<RateLimitConfig>,
+}
+
+impl EnrichConfig {
+    pub fn from_section<C: Config>(section: &mut Section<C>) -> Option<Self> {
+        let rpc_uris = rpc_uris(section);
+        let cache_size = section.get_or("cache_size", 10_000_usize);
+        if cache_size == 0 {
+            section.error("cache_size", "must be greater than 0");
+        }
+        let retry = retry(section);
+        let rate_limit = rate_limit(section);
+        Some(Self {
+            rpc_uris: rpc_uris?,
+            cache_size,
+            retry,
+            rate_limit,
+        })
+    }
+}
+
+/// 按事件的 `transaction_hash` 附加交易回执与区块中的字段：
+/// `tx_from`、`tx_gas_used`、`tx_effective_gas_price`、`tx_status` 与 `block_timestamp`
+///
+/// 字段加上前缀，不覆盖 `Transfer` 等事件自身的 `from`。一批事件中缺少的回执与区块
+/// 分别通过一次 JSON-RPC 批量请求读取，读取到的结果会被缓存。没有 `transaction_hash` 的事件原样输出。
+pub struct EnrichProcessor {
+    pool: RpcPool,
+    retry: RetryPolicy,
+    receipts: Mutex<Cache<H256, Receipt>>,
+    /// 区块号到区块时间戳
+    timestamps: Mutex<Cache<u64, u64>>,
+}
+
+/// 交易回执中需要附加的字段
+#[derive(Debug, Clone)]
+struct Receipt {
+    from: Address,
+    gas_used: Option<U256>,
+    /// 伦敦升级之前的节点不返回
+    effective_gas_price: Option<U256>,
+    status: Option<U64>,
+    block_number: Option<U64>,
+}
+
+/// 容量固定的缓存，超出容量时先淘汰最早加入的项
+struct Cache<K, V> {
+    capacity: usize,
+    entries: HashMap<K, V>,
+    order: VecDeque<K>,
+}
+
+impl<K: Hash + Eq + Clone, V: Clone> Cache<K, V> {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: HashMap::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    fn get(&self, key: &K) -> Option<V> {
+        self.entries.get(key).cloned()
+    }
+
+    fn insert(&mut self, key: K, value: V) {
+        if self.entries.insert(key.clone(), value).is_none() {
+            self.order.push_back(key);
+        }
+        while self.order.len() > self.capacity {
+            if let Some(key) = self.order.pop_front() {
+                self.entries.remove(&key);
+            }
+        }
+    }
+}
+
+impl EnrichProcessor {
+    pub fn new(config: EnrichConfig) -> Result<Self> {
+        Ok(Self {
+            pool: RpcPool::new(&config.rpc_uris, config.rate_limit.as_ref())?,
+            retry: config.retry,
+            receipts: Mutex::new(Cache::new(config.cache_size)),
+            timestamps: Mutex::new(Cache::new(config.cache_size)),
+        })
+    }
+
+    /// 读取回执，先查缓存，缺少的通过一次批量请求读取
+    async fn receipts(&self, hashes: HashSet<H256>) -> Result<HashMap<H256, Receipt>> {
+        let (mut found, missing) = lookup(&self.receipts, hashes);
+        let params: Vec<Vec<Json>> = missing
+            .iter()
+            .map(|hash| vec![helpers::serialize(hash)])
+            .collect();
+        let fetched = self
+            .batch("eth_getTransactionReceipt", &params, |index, value| {
+                let effective_gas_price = match value.get("effectiveGasPrice") {
+                    Some(price) => helpers::decode::<Option<U256>>(price.clone())?,
+                    None => None,
+                };
+                let receipt =
+                    helpers::decode::<Option<TransactionReceipt>>(value)?.ok_or_else(|| {
+                        Error::unknown_block(&format!(
+                            "receipt of transaction {:?} not found",
+                            missing[index]
+                        ))
+                    })?;
+                Ok(Receipt {
+                    from: receipt.from,
+                    gas_used: receipt.gas_used,
+                    effective_gas_price,
+                    status: receipt.status,
+                    block_number: receipt.block_number,
+                })
+            })
+            .await?;
+
+        let mut cache = self.receipts.lock().unwrap();
+        for (hash, receipt) in missing.into_iter().zip(fetched) {
+            cache.insert(hash, receipt.clone());
+            found.insert(hash, receipt);
+        }
+        Ok(found)
+    }
+
+    /// 读取区块时间戳，先查缓存，缺少的通过一次批量请求读取
+    async fn timestamps(&self, numbers: HashSet<u64>) -> Result<HashMap<u64, u64>> {
+        let (mut found, missing) = lookup(&self.timestamps, numbers);
+        let params: Vec<Vec<Json>> = missing
+            .iter()
+            .map(|number| vec![helpers::serialize(&U64::from(*number)), Json::Bool(false)])
+            .collect();
+        let fetched = self
+            .batch("eth_getBlockByNumber", &params, |index, value| {
+                let block = helpers::decode::<Option<Block<H256>>>(value)?.ok_or_else(|| {
+                    Error::unknown_block(&format!("block {} not found", missing[index]))
+                })?;
+                Ok(block.timestamp.low_u64())
+            })
+            .await?;
+
+        let mut cache = self.timestamps.lock().unwrap();
+        for (number, timestamp) in missing.into_iter().zip(fetched) {
+            cache.insert(number, timestamp);
+            found.insert(number, timestamp);
+        }
+        Ok(found)
+    }
+
+    /// 以相同的方法发送一次批量请求，任意一项失败时按重试策略重新发送整批请求
+    async fn batch<T, F>(&self, method: &str, params: &[Vec<Json>], decode: F) -> Result<Vec<T>>
+    where
+        F: Fn(usize, Json) -> Result<T>,
+    {
+        if params.is_empty() {
+            return Ok(vec![]);
+        }
+        let result = self
+            .retry
+            .retry(|| async {
+                let requests: Vec<_> = params
+                    .iter()
+                    .map(|params| self.pool.prepare(method, params.clone()))
+                    .collect();
+                let results = self.pool.send_batch(requests).await?;
+                results
+                    .into_iter()
+                    .enumerate()
+                    .map(|(index, result)| decode(index, result?))
+                    .collect::<Result<Vec<T>>>()
+            })
+            .await;
+        result.with_context(|| format!("while fetching {} x{}", method, params.len()))
+    }
+}
+
+/// 从缓存中取出已有的值，返回已有的值与缺少的键
+fn lookup<K: Hash + Eq + Clone, V: Clone>(
+    cache: &Mutex<Cache<K, V>>,
+    keys: HashSet<K>,
+) -> (HashMap<K, V>, Vec<K>) {
+    let cache = cache.lock().unwrap();
+    let mut found = HashMap::new();
+    let mut missing = vec![];
+    for key in keys {
+        match cache.get(&key) {
+            Some(value) => {
+                found.insert(key, value);
+            }
+            None => missing.push(key),
+        }
+    }
+    (found, missing)
+}
+
+/// 事件中的交易哈希，没有或格式不对时返回 `None`
+fn transaction_hash(event: &Event) -> Option<H256> {
+    match event.get("transaction_hash") {
+        Some(Value::String(hash)) => match H256::from_str(hash) {
+            Ok(hash) => Some(hash),
+            Err(err) => {
+                log::warn!("skip enrichment for transaction hash {} - {}", hash, err);
+                None
+            }
+        },
+        _ => None,
+    }
+}
+
+#[async_trait]
+impl Processor for EnrichProcessor {
+    async fn process(&self, mut events: Vec<Event>) -> Result<Vec<Event>> {
+        let hashes: Vec<Option<H256>> = events.iter().map(transaction_hash).collect();
+        let receipts = self
+            .receipts(hashes.iter().flatten().copied().collect())
+            .await?;
+        let numbers: HashSet<u64> = receipts
+            .values()
+            .filter_map(|receipt| receipt.block_number.map(|number| number.as_u64()))
+            .collect();
+        let timestamps = self.timestamps(numbers).await?;
+
+        for (event, hash) in events.iter_mut().zip(hashes) {
+            let receipt = match hash.and_then(|hash| receipts.get(&hash)) {
+                Some(receipt) => receipt,
+                None => continue,
+            };
+            let timestamp = receipt
+                .block_number
+                .and_then(|number| timestamps.get(&number.as_u64()));
+            event.insert(
+                "tx_from".to_owned(),
+                Value::from(format!("{:?}", receipt.from)),
+            );
+            event.insert(
+                "tx_gas_used".to_owned(),
+                Value::from(receipt.gas_used.map(|gas| gas.low_u64() as i64)),
+            );
+            event.insert(
+                "tx_effective_gas_price".to_owned(),
+                Value::from(receipt.effective_gas_price.map(|price| price.to_string())),
+            );
+            event.insert(
+                "tx_status".to_owned(),
+                Value::from(receipt.status.map(|status| status.as_u64() as i64)),
+            );
+            event.insert(
+                "block_timestamp".to_owned(),
+                Value::from(timestamp.map(|timestamp| *timestamp as i64)),
+            );
+        }
+        Ok(events)
+    }
+}
+
+#[test]
+fn test_cache() {
+    let mut cache = Cache::new(2);
+    cache.insert(1, "a");
+    cache.insert(2, "b");
+    cache.insert(1, "c");
+    assert_eq!(Some("c"), cache.get(&1));
+    cache.insert(3, "d");
+    assert_eq!(None, cache.get(&1));
+    assert_eq!(Some("b"), cache.get(&2));
+    assert_eq!(Some("d"), cache.get(&3));
+}
+
+#[tokio::test]
+async fn test_enrich() {
+    use crate::input::web3_rpc::test_block;
+    use crate::rpc::mock::{parse_quantity, quantity, serve_recorded, Reply};
+    use std::sync::Arc;
+
+    let hash = |value: u64| format!("{:?}", H256::from_low_u64_be(value));
+    let calls = Arc::new(Mutex::new(HashMap::<String, usize>::new()));
+    let (uri, requests) = serve_recorded({
+        let calls = calls.clone();
+        move |method, params| {
+            *calls.lock().unwrap().entry(method.clone()).or_default() += 1;
+            async move {
+                match method.as_str() {
+                    "eth_getTransactionReceipt" => {
+                        let tx = H256::from_str(params[0].as_str().unwrap()).unwrap();
+                        let tx = tx.to_low_u64_be();
+                        if tx == 0 {
+                            return Reply::Result(Json::Null);
+                        }
+                        Reply::Result(serde_json::json!({
+                            "transactionHash": hash(tx),
+                            "transactionIndex": "0x0",
+                            "blockHash": hash(tx / 10),
+                            "blockNumber": quantity(tx / 10),
+                            "from": format!("{:?}", Address::from_low_u64_be(tx)),
+                            "to": null,
+                            "cumulativeGasUsed": quantity(50_000),
+                            "gasUsed": quantity(21_000 + tx),
+                            "contractAddress": null,
+                            "logs": [],
+                            "status": quantity(tx % 2),
+                            "logsBloom": format!("0x{}", "0".repeat(512)),
+                            "effectiveGasPrice": quantity(1_000_000_000),
+                        }))
+                    }
+                    "eth_getBlockByNumber" => {
+                        Reply::Result(test_block(parse_quantity(&params[0]), false))
+                    }
+                    _ => Reply::Error(-32601, "method not found".to_owned()),
+                }
+            }
+        }
+    })
+    .await;
+
+    let processor = EnrichProcessor::new(EnrichConfig {
+        rpc_uris: vec![Secret::new(&uri)],
+        cache_size: 10,
+        retry: RetryPolicy {
+            initial_interval: 1,
+            max_elapsed: 50,
+            ..RetryPolicy::default()
+        },
+        rate_limit: None,
+    })
+    .unwrap();
+    let event = |tx: Option<u64>| {
+        let mut event = Event::new();
+        event.insert("from".to_owned(), Value::from("token sender"));
+        event.insert("transaction_hash".to_owned(), Value::from(tx.map(hash)));
+        event
+    };
+    let events = vec![
+        event(Some(51)),
+        event(Some(51)),
+        event(Some(60)),
+        event(None),
+    ];
+
+    let enriched = processor.process(events.clone()).await.unwrap();
+    assert_eq!(Value::from("token sender"), enriched[0]["from"]);
+    assert_eq!(
+        Value::from(format!("{:?}", Address::from_low_u64_be(51))),
+        enriched[0]["tx_from"]
+    );
+    assert_eq!(Value::Integer(21_051), enriched[1]["tx_gas_used"]);
+    assert_eq!(
+        Value::from("1000000000"),
+        enriched[1]["tx_effective_gas_price"]
+    );
+    assert_eq!(Value::Integer(1), enriched[0]["tx_status"]);
+    assert_eq!(Value::Integer(0), enriched[2]["tx_status"]);
+    assert_eq!(
+        Value::Integer(1_600_000_060),
+        enriched[0]["block_timestamp"]
+    );
+    assert_eq!(
+        Value::Integer(1_600_000_072),
+        enriched[2]["block_timestamp"]
+    );
+    assert_eq!(events[3], enriched[3]);
+
+    let counts = |calls: &Mutex<HashMap<String, usize>>| {
+        let calls = calls.lock().unwrap();
+        (
+            calls["eth_getTransactionReceipt"],
+            calls["eth_getBlockByNumber"],
+        )
+    };
+    assert_eq!((2, 2), counts(&calls));
+    // 每种查询在一个批量请求中发送
+    let batch = |method: &str| vec![method.to_owned(), method.to_owned()];
+    assert_eq!(
+        vec![
+            batch("eth_getTransactionReceipt"),
+            batch("eth_getBlockByNumber")
+        ],
+        *requests.lock().unwrap()
+    );
+    // 第二次全部命中缓存
+    assert_eq!(enriched, processor.process(events).await.unwrap());
+    assert_eq!((2, 2), counts(&calls));
+    assert_eq!(2, requests.lock().unwrap().len());
+
+    let err = processor.process(vec![event(Some(0))]).await.unwrap_err();
+    assert!(err.is_unknown_block());
+}
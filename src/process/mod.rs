@@ -1,4 +1,61 @@
+pub mod enrich;
+pub mod validate;
 
-pub trait Processor {
-    
-}
\ No newline at end of file
+use async_trait::async_trait;
+use tokio::sync::mpsc::Sender;
+
+use crate::{config::Section, event::Event, Config, Result};
+
+use self::enrich::{EnrichConfig, EnrichProcessor};
+use self::validate::{ValidateConfig, ValidateProcessor};
+
+/// 事件处理器，输入在解码后、输出前按批次调用
+#[async_trait]
+pub trait Processor: Send + Sync {
+    /// 处理一批事件，返回需要继续向下游发送的事件
+    async fn process(&self, events: Vec<Event>) -> Result<Vec<Event>>;
+}
+
+/// `[processor.<name>]` 的配置，`name` 决定处理器类型
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProcessorConfig {
+    Enrich(EnrichConfig),
+    Validate(ValidateConfig),
+}
+
+impl ProcessorConfig {
+    /// 读取 `[processor]` 下的所有处理器，按名称排序
+    pub fn from_section<C: Config>(section: &mut Section<C>) -> Option<Vec<Self>> {
+        let mut processors = vec![];
+        let mut valid = true;
+        for name in section.keys() {
+            let mut processor = section.section(&name);
+            let config = match name.as_str() {
+                "enrich" => EnrichConfig::from_section(&mut processor).map(ProcessorConfig::Enrich),
+                "validate" => {
+                    ValidateConfig::from_section(&mut processor).map(ProcessorConfig::Validate)
+                }
+                _ => {
+                    processor.error("", "unknown processor, expects enrich or validate");
+                    continue;
+                }
+            };
+            processor.finish();
+            match config {
+                Some(config) => processors.push(config),
+                None => valid = false,
+            }
+        }
+        valid.then_some(processors)
+    }
+
+    /// 创建处理器，`dead_letter` 为死信输出的发送端
+    pub fn build(&self, dead_letter: Option<Sender<Event>>) -> Result<Box<dyn Processor>> {
+        match self {
+            ProcessorConfig::Enrich(config) => Ok(Box::new(EnrichProcessor::new(config.clone())?)),
+            ProcessorConfig::Validate(config) => Ok(Box::new(
+                ValidateProcessor::from_validate_config(config.clone(), dead_letter)?,
+            )),
+        }
+    }
+}
@@ -0,0 +1,210 @@
+use std::str::FromStr;
+
+use async_trait::async_trait;
+use tokio::sync::mpsc::Sender;
+
+use super::Processor;
+use crate::config::{ConfigErrors, Section};
+use crate::{event::Event, schema::EventSchema, Config, Error, Result, Value};
+
+/// 事件不符合结构定义时的处理方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValidateMode {
+    /// 丢弃不符合的事件，符合的事件补齐默认值
+    Reject,
+    /// 补齐默认值并转换字段类型，无法转换时丢弃
+    Coerce,
+    /// 将不符合的事件发送到死信输出，符合的事件补齐默认值
+    DeadLetter,
+}
+
+impl FromStr for ValidateMode {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "reject" => Ok(ValidateMode::Reject),
+            "coerce" => Ok(ValidateMode::Coerce),
+            "dead_letter" => Ok(ValidateMode::DeadLetter),
+            _ => Err(Error::invalid_param(&format!(
+                "failed to parse str {} for ValidateMode",
+                s
+            ))),
+        }
+    }
+}
+
+/// 死信事件中记录校验错误的字段名
+pub const DEAD_LETTER_ERROR: &str = "_error";
+
+/// 按照事件结构校验事件
+pub struct ValidateProcessor {
+    schema: EventSchema,
+    mode: ValidateMode,
+    dead_letter: Option<Sender<Event>>,
+}
+
+impl ValidateProcessor {
+    pub fn new(
+        schema: EventSchema,
+        mode: ValidateMode,
+        dead_letter: Option<Sender<Event>>,
+    ) -> Self {
+        Self {
+            schema,
+            mode,
+            dead_letter,
+        }
+    }
+
+    /// 从 `processor.validate` 读取配置，结构定义来自 `fields` 表或 `abi` + `event`
+    pub fn from_config<C: Config>(config: &C, dead_letter: Option<Sender<Event>>) -> Result<Self> {
+        let mut errors = ConfigErrors::new();
+        let mut section = Section::new(config, "processor.validate", &mut errors);
+        let validate = ValidateConfig::from_section(&mut section);
+        section.finish();
+        let validate = errors.into_result(validate)?;
+        Self::from_validate_config(validate, dead_letter)
+    }
+
+    pub fn from_validate_config(
+        config: ValidateConfig,
+        dead_letter: Option<Sender<Event>>,
+    ) -> Result<Self> {
+        if config.mode == ValidateMode::DeadLetter && dead_letter.is_none() {
+            return Err(Error::invalid_param(
+                "processor.validate.mode is dead_letter but no dead letter output is configured",
+            ));
+        }
+        Ok(Self::new(config.schema, config.mode, dead_letter))
+    }
+}
+
+/// `[processor.validate]` 的配置
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidateConfig {
+    pub mode: ValidateMode,
+    pub schema: EventSchema,
+}
+
+impl ValidateConfig {
+    pub fn from_section<C: Config>(section: &mut Section<C>) -> Option<Self> {
+        let mode = section.required::<String>("mode");
+        let mode = mode.and_then(|mode| section.check("mode", mode.parse()));
+
+        let schema = match section.optional::<String>("abi") {
+            Some(abi) => {
+                let event = section.required::<String>("event")?;
+                section.check("abi", EventSchema::from_abi_path(abi, &event))
+            }
+            None => {
+                let mut fields = section.section("fields");
+                let schema = EventSchema::from_section(&mut fields);
+                fields.finish();
+                schema
+            }
+        };
+        Some(Self {
+            mode: mode?,
+            schema: schema?,
+        })
+    }
+}
+
+#[async_trait]
+impl Processor for ValidateProcessor {
+    async fn process(&self, events: Vec<Event>) -> Result<Vec<Event>> {
+        let mut valid = Vec::with_capacity(events.len());
+        for event in events {
+            let result = match self.mode {
+                ValidateMode::Coerce => self.schema.coerce(event.clone()),
+                _ => self
+                    .schema
+                    .validate(&event)
+                    .map(|_| self.schema.fill_defaults(event.clone())),
+            };
+            match (result, &self.dead_letter) {
+                (Ok(event), _) => valid.push(event),
+                (Err(err), Some(dead_letter)) if self.mode == ValidateMode::DeadLetter => {
+                    let mut event = event;
+                    event.insert(DEAD_LETTER_ERROR.to_owned(), Value::from(err.get_msg()));
+                    dead_letter.send(event).await?;
+                }
+                (Err(err), _) => log::warn!("drop invalid event {:?} - {}", event, err),
+            }
+        }
+        Ok(valid)
+    }
+}
+
+#[cfg(test)]
+fn events() -> Vec<Event> {
+    let mut valid = Event::new();
+    valid.insert("token_id".to_owned(), Value::from(10));
+    let mut drift = Event::new();
+    drift.insert("token_id".to_owned(), Value::from("11"));
+    let mut broken = Event::new();
+    broken.insert("token_id".to_owned(), Value::from("eleven"));
+    vec![valid, drift, broken]
+}
+
+#[cfg(test)]
+fn processor(mode: &str, dead_letter: Option<Sender<Event>>) -> Result<ValidateProcessor> {
+    let config = crate::config::TomlConfig::from_string(&format!(
+        r#"
+        [processor.validate]
+        mode = "{}"
+        [processor.validate.fields]
+        token_id = "integer"
+        memo = {{ type = "string", default = "" }}
+        "#,
+        mode
+    ))?;
+    ValidateProcessor::from_config(&config, dead_letter)
+}
+
+#[tokio::test]
+async fn test_reject() {
+    let events = processor("reject", None)
+        .unwrap()
+        .process(events())
+        .await
+        .unwrap();
+    assert_eq!(1, events.len());
+    assert_eq!(Some(&Value::Integer(10)), events[0].get("token_id"));
+    assert_eq!(Some(&Value::from("")), events[0].get("memo"));
+}
+
+#[tokio::test]
+async fn test_coerce() {
+    let events = processor("coerce", None)
+        .unwrap()
+        .process(events())
+        .await
+        .unwrap();
+    assert_eq!(2, events.len());
+    assert_eq!(Some(&Value::Integer(11)), events[1].get("token_id"));
+}
+
+#[tokio::test]
+async fn test_dead_letter() {
+    assert!(processor("dead_letter", None).is_err());
+    assert!(processor("drop", None).is_err());
+
+    let (sender, mut reciver) = tokio::sync::mpsc::channel(10);
+    let events = processor("dead_letter", Some(sender))
+        .unwrap()
+        .process(events())
+        .await
+        .unwrap();
+    assert_eq!(1, events.len());
+
+    let event = reciver.recv().await.unwrap();
+    assert_eq!(Some(&Value::from("11")), event.get("token_id"));
+    assert_eq!(
+        Some(&Value::from(
+            "field token_id expects Integer but got String"
+        )),
+        event.get(DEAD_LETTER_ERROR)
+    );
+    assert!(reciver.recv().await.is_some());
+}
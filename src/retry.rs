@@ -0,0 +1,200 @@
+use std::collections::hash_map::RandomState;
+use std::future::Future;
+use std::hash::{BuildHasher, Hasher};
+use std::time::{Duration, Instant};
+
+use tokio::time::sleep;
+
+use crate::config::Section;
+use crate::{Config, Error, Result};
+
+/// 重试策略，等待时间按指数增长并加入随机抖动
+///
+/// 只重试 `Error::is_retryable` 的错误，其他错误直接返回。
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    /// 第一次重试前等待的时间（毫秒）
+    pub initial_interval: u64,
+    /// 单次等待时间的上限（毫秒）
+    pub max_interval: u64,
+    /// 每次重试后等待时间的倍数
+    pub multiplier: f64,
+    /// 抖动比例，等待时间在 `[interval * (1 - jitter), interval]` 之间
+    pub jitter: f64,
+    /// 从第一次调用开始的最长重试时间（毫秒），为 0 时不限制
+    pub max_elapsed: u64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            initial_interval: 500,
+            max_interval: 30_000,
+            multiplier: 2.0,
+            jitter: 0.5,
+            max_elapsed: 120_000,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// 读取 `retry` 子表，未配置的项使用默认值
+    pub fn from_section<C: Config>(section: &mut Section<C>) -> Self {
+        let default = Self::default();
+        let policy = Self {
+            initial_interval: section.get_or("initial_interval", default.initial_interval),
+            max_interval: section.get_or("max_interval", default.max_interval),
+            multiplier: section.get_or("multiplier", default.multiplier),
+            jitter: section.get_or("jitter", default.jitter),
+            max_elapsed: section.get_or("max_elapsed", default.max_elapsed),
+        };
+        if policy.initial_interval == 0 {
+            section.error("initial_interval", "must be greater than 0");
+        }
+        if policy.max_interval < policy.initial_interval {
+            section.error("max_interval", "must not be less than initial_interval");
+        }
+        if policy.multiplier < 1.0 {
+            section.error("multiplier", "must not be less than 1");
+        }
+        if !(0.0..=1.0).contains(&policy.jitter) {
+            section.error("jitter", "must be between 0 and 1");
+        }
+        policy
+    }
+
+    pub fn backoff(&self) -> Backoff {
+        Backoff {
+            policy: self.clone(),
+            interval: self.initial_interval as f64,
+            start: Instant::now(),
+        }
+    }
+
+    /// 调用 `f` 直到成功、遇到不可重试的错误或超过 `max_elapsed`
+    pub async fn retry<T, E, F, Fut>(&self, mut f: F) -> Result<T>
+    where
+        E: Into<Error>,
+        F: FnMut() -> Fut,
+        Fut: Future<Output = std::result::Result<T, E>>,
+    {
+        let mut backoff = self.backoff();
+        loop {
+            let err = match f().await {
+                Ok(value) => return Ok(value),
+                Err(err) => err.into(),
+            };
+            match backoff.next_delay(&err) {
+                Some(delay) => {
+                    log::warn!("retry in {}ms - {}", delay.as_millis(), err);
+                    sleep(delay).await;
+                }
+                None => return Err(err),
+            }
+        }
+    }
+}
+
+/// 一次调用的重试状态
+pub struct Backoff {
+    policy: RetryPolicy,
+    interval: f64,
+    start: Instant,
+}
+
+impl Backoff {
+    /// 下一次重试前等待的时间，不应重试时返回 `None`
+    pub fn next_delay(&mut self, err: &Error) -> Option<Duration> {
+        if !err.is_retryable() {
+            return None;
+        }
+        let interval = self.interval.min(self.policy.max_interval as f64);
+        self.interval = interval * self.policy.multiplier;
+        let delay =
+            Duration::from_millis((interval * (1.0 - self.policy.jitter * random())) as u64);
+
+        let max_elapsed = Duration::from_millis(self.policy.max_elapsed);
+        if self.policy.max_elapsed > 0 && self.start.elapsed() + delay > max_elapsed {
+            return None;
+        }
+        Some(delay)
+    }
+}
+
+/// `[0, 1)` 之间的随机数，只用于抖动
+fn random() -> f64 {
+    let value = RandomState::new().build_hasher().finish();
+    (value >> 11) as f64 / (1_u64 << 53) as f64
+}
+
+#[test]
+fn test_backoff() {
+    let policy = RetryPolicy {
+        initial_interval: 100,
+        max_interval: 400,
+        multiplier: 2.0,
+        jitter: 0.0,
+        max_elapsed: 0,
+    };
+    let timeout = Error::timeout("read timed out");
+    let mut backoff = policy.backoff();
+    let delays: Vec<u64> = (0..5)
+        .map(|_| backoff.next_delay(&timeout).unwrap().as_millis() as u64)
+        .collect();
+    assert_eq!(vec![100, 200, 400, 400, 400], delays);
+    assert_eq!(None, backoff.next_delay(&Error::invalid_param("bad")));
+
+    let mut backoff = RetryPolicy {
+        jitter: 0.5,
+        ..policy.clone()
+    }
+    .backoff();
+    for _ in 0..20 {
+        let delay = backoff.next_delay(&timeout).unwrap().as_millis();
+        assert!((50..=400).contains(&delay));
+    }
+
+    let mut backoff = RetryPolicy {
+        max_elapsed: 250,
+        ..policy
+    }
+    .backoff();
+    assert!(backoff.next_delay(&timeout).is_some());
+    assert!(backoff.next_delay(&timeout).is_some());
+    assert_eq!(None, backoff.next_delay(&timeout));
+}
+
+#[tokio::test]
+async fn test_retry() {
+    let policy = RetryPolicy {
+        initial_interval: 1,
+        max_interval: 10,
+        multiplier: 2.0,
+        jitter: 0.5,
+        max_elapsed: 1000,
+    };
+
+    let mut calls = 0;
+    let value = policy
+        .retry(|| {
+            calls += 1;
+            let result = if calls < 3 {
+                Err(Error::connection_reset("connection reset by peer"))
+            } else {
+                Ok(calls)
+            };
+            async move { result }
+        })
+        .await;
+    assert_eq!(Ok(3), value);
+
+    let mut calls = 0;
+    let value: Result<()> = policy
+        .retry(|| {
+            calls += 1;
+            async { Err(Error::invalid_param("bad filter")) }
+        })
+        .await;
+    assert!(value.unwrap_err().is_invalid_err());
+    assert_eq!(1, calls);
+}
@@ -0,0 +1,159 @@
+//! 测试用的 JSON-RPC 服务，只实现 HTTP/1.1 POST 与批量请求
+
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+
+use serde_json::{json, Value};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+
+/// 对一次调用的回复
+pub(crate) enum Reply {
+    Result(Value),
+    Error(i64, String),
+    /// 直接返回 HTTP 状态码，如 429
+    Status(u16),
+}
+
+/// 每个 HTTP 请求中的方法名，单个调用也记录为只有一个方法的列表
+pub(crate) type Requests = Arc<Mutex<Vec<Vec<String>>>>;
+
+/// 启动服务，`handler` 收到方法名与参数，返回服务地址
+pub(crate) async fn serve<F, Fut>(handler: F) -> String
+where
+    F: Fn(String, Vec<Value>) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Reply> + Send + 'static,
+{
+    serve_recorded(handler).await.0
+}
+
+/// 与 `serve` 相同，同时按 HTTP 请求记录收到的方法，用于检查批量请求
+pub(crate) async fn serve_recorded<F, Fut>(handler: F) -> (String, Requests)
+where
+    F: Fn(String, Vec<Value>) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Reply> + Send + 'static,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr: SocketAddr = listener.local_addr().unwrap();
+    let handler = Arc::new(handler);
+    let requests = Requests::default();
+    tokio::spawn({
+        let requests = requests.clone();
+        async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(connection(stream, handler.clone(), requests.clone()));
+            }
+        }
+    });
+    (format!("http://{}", addr), requests)
+}
+
+async fn connection<F, Fut>(stream: TcpStream, handler: Arc<F>, requests: Requests)
+where
+    F: Fn(String, Vec<Value>) -> Fut,
+    Fut: Future<Output = Reply>,
+{
+    let mut stream = BufReader::new(stream);
+    loop {
+        let mut length = 0;
+        loop {
+            let mut line = String::new();
+            match stream.read_line(&mut line).await {
+                Ok(0) | Err(_) => return,
+                Ok(_) => {}
+            }
+            let line = line.trim_end();
+            if line.is_empty() {
+                break;
+            }
+            if let Some((name, value)) = line.split_once(':') {
+                if name.eq_ignore_ascii_case("content-length") {
+                    length = value.trim().parse().unwrap();
+                }
+            }
+        }
+        let mut body = vec![0; length];
+        if stream.read_exact(&mut body).await.is_err() {
+            return;
+        }
+
+        let request: Value = serde_json::from_slice(&body).unwrap();
+        let methods = match &request {
+            Value::Array(calls) => calls.iter().map(method).collect(),
+            call => vec![method(call)],
+        };
+        requests.lock().unwrap().push(methods);
+        let (status, body) = match request {
+            Value::Array(calls) => {
+                let mut replies = vec![];
+                let mut status = 200;
+                for call in calls {
+                    match reply(&*handler, call).await {
+                        Ok(reply) => replies.push(reply),
+                        Err(code) => {
+                            status = code;
+                            break;
+                        }
+                    }
+                }
+                match status {
+                    200 => (status, Value::Array(replies).to_string()),
+                    _ => (status, String::new()),
+                }
+            }
+            call => match reply(&*handler, call).await {
+                Ok(reply) => (200, reply.to_string()),
+                Err(status) => (status, String::new()),
+            },
+        };
+        let response = format!(
+            "HTTP/1.1 {} MOCK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+            status,
+            body.len(),
+            body
+        );
+        if stream
+            .get_mut()
+            .write_all(response.as_bytes())
+            .await
+            .is_err()
+        {
+            return;
+        }
+    }
+}
+
+/// 返回 JSON-RPC 响应，需要返回 HTTP 状态码时返回 `Err`
+async fn reply<F, Fut>(handler: &F, call: Value) -> std::result::Result<Value, u16>
+where
+    F: Fn(String, Vec<Value>) -> Fut,
+    Fut: Future<Output = Reply>,
+{
+    let method = method(&call);
+    let params = call["params"].as_array().cloned().unwrap_or_default();
+    let id = call["id"].clone();
+    match handler(method, params).await {
+        Reply::Result(result) => Ok(json!({ "jsonrpc": "2.0", "id": id, "result": result })),
+        Reply::Error(code, message) => Ok(json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": { "code": code, "message": message },
+        })),
+        Reply::Status(status) => Err(status),
+    }
+}
+
+fn method(call: &Value) -> String {
+    call["method"].as_str().unwrap_or_default().to_owned()
+}
+
+/// 十六进制的数量，如区块号
+pub(crate) fn quantity(value: u64) -> Value {
+    json!(format!("{:#x}", value))
+}
+
+/// 解析十六进制的数量
+pub(crate) fn parse_quantity(value: &Value) -> u64 {
+    u64::from_str_radix(value.as_str().unwrap().trim_start_matches("0x"), 16).unwrap()
+}
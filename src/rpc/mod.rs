@@ -0,0 +1,7 @@
+pub mod limit;
+#[cfg(test)]
+pub(crate) mod mock;
+pub mod pool;
+
+pub use limit::{RateLimitConfig, RateLimiter};
+pub use pool::RpcPool;
@@ -0,0 +1,563 @@
+use std::fmt::Debug;
+use std::future::Future;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use jsonrpc_core::{Call, Value};
+use web3::futures::future::{join_all, BoxFuture};
+use web3::transports::Http;
+use web3::types::{U256, U64};
+use web3::{helpers, BatchTransport, RequestId, Transport};
+
+use super::limit::{RateLimitConfig, RateLimiter};
+use crate::config::Secret;
+use crate::{Error, Result};
+
+/// 延迟与错误率的滑动平均系数
+const ALPHA: f64 = 0.3;
+/// 错误率每经过这段时间减半，出错的节点之后还能重新被选中
+const ERROR_HALF_LIFE: Duration = Duration::from_secs(30);
+/// 错误率为 1 时延迟按 `1 + ERROR_WEIGHT` 倍计算
+const ERROR_WEIGHT: f64 = 10.0;
+/// 每落后一个区块相当于增加的延迟（毫秒）
+const LAG_PENALTY: f64 = 1000.0;
+
+/// 节点的健康状况
+#[derive(Debug)]
+struct Health {
+    /// 平均延迟（毫秒）
+    latency: f64,
+    error_rate: f64,
+    updated: Instant,
+    /// 最近一次读取到的最新区块
+    head: u64,
+}
+
+impl Health {
+    fn new() -> Self {
+        Self {
+            latency: 0.0,
+            error_rate: 0.0,
+            updated: Instant::now(),
+            head: 0,
+        }
+    }
+
+    fn error_rate(&self, now: Instant) -> f64 {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.error_rate * 0.5_f64.powf(elapsed / ERROR_HALF_LIFE.as_secs_f64())
+    }
+
+    fn record(&mut self, latency: Duration, failed: bool) {
+        let now = Instant::now();
+        let failed = if failed { 1.0 } else { 0.0 };
+        self.error_rate = self.error_rate(now) * (1.0 - ALPHA) + failed * ALPHA;
+        self.latency = self.latency * (1.0 - ALPHA) + latency.as_secs_f64() * 1000.0 * ALPHA;
+        self.updated = now;
+    }
+
+    /// 分数越低越好
+    fn score(&self, max_head: u64, now: Instant) -> f64 {
+        let lag = max_head.saturating_sub(self.head) as f64;
+        self.latency * (1.0 + ERROR_WEIGHT * self.error_rate(now)) + lag * LAG_PENALTY
+    }
+}
+
+struct Endpoint {
+    uri: Secret,
+    http: Http,
+    health: Mutex<Health>,
+    limiter: Option<Arc<RateLimiter>>,
+    chain: Mutex<ChainCheck>,
+}
+
+/// 节点链 ID 的检查结果，只有 `Verified` 的节点参与排序
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ChainCheck {
+    /// 链 ID 一致，或者还没有检查过
+    Verified,
+    /// 检查时无法连接，读取最新区块时重新检查
+    Unreachable,
+    /// 恢复连接后发现链 ID 不一致，不再使用
+    Mismatched,
+}
+
+/// 多个 RPC 节点组成的连接池，作为 `Web3` 的 transport 使用
+///
+/// 请求优先发送到健康分数最好的节点（延迟、错误率与落后的区块数），
+/// 节点返回可重试的错误时依次换到下一个节点，批量请求整体切换。错误中的节点地址会被隐藏。
+/// 配置限流时，同一个地址的请求共用一个令牌桶。
+/// 检查链 ID 时无法连接的节点暂不使用，之后每次读取最新区块时重新检查。
+#[derive(Clone)]
+pub struct RpcPool {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    endpoints: Vec<Endpoint>,
+    next_id: AtomicUsize,
+    /// 检查通过的链 ID
+    chain_id: Mutex<Option<U256>>,
+}
+
+impl RpcPool {
+    pub fn new(uris: &[Secret], rate_limit: Option<&RateLimitConfig>) -> Result<Self> {
+        if uris.is_empty() {
+            return Err(Error::invalid_param("requires at least one rpc uri"));
+        }
+        let endpoints = uris
+            .iter()
+            .map(|uri| {
+                let http = Http::new(uri.expose()).map_err(|err| uri.redact(err.into()))?;
+                Ok(Endpoint {
+                    uri: uri.clone(),
+                    http,
+                    health: Mutex::new(Health::new()),
+                    limiter: rate_limit.map(|config| RateLimiter::shared(uri, config)),
+                    chain: Mutex::new(ChainCheck::Verified),
+                })
+            })
+            .collect::<Result<Vec<Endpoint>>>()?;
+        Ok(Self {
+            inner: Arc::new(Inner {
+                endpoints,
+                next_id: AtomicUsize::new(0),
+                chain_id: Mutex::new(None),
+            }),
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.endpoints.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inner.endpoints.is_empty()
+    }
+
+    /// 链 ID 检查结果为 `check` 的节点序号
+    fn checked(&self, check: ChainCheck) -> Vec<usize> {
+        (0..self.len())
+            .filter(|index| *self.inner.endpoints[*index].chain.lock().unwrap() == check)
+            .collect()
+    }
+
+    /// 按健康分数从好到坏排列的节点序号，分数相同时按配置顺序，不包括未确认链 ID 的节点
+    pub fn ranked(&self) -> Vec<usize> {
+        let now = Instant::now();
+        let healths: Vec<_> = self
+            .inner
+            .endpoints
+            .iter()
+            .map(|endpoint| endpoint.health.lock().unwrap())
+            .collect();
+        let max_head = healths.iter().map(|health| health.head).max().unwrap_or(0);
+        let scores: Vec<f64> = healths
+            .iter()
+            .map(|health| health.score(max_head, now))
+            .collect();
+        let mut ranked = self.checked(ChainCheck::Verified);
+        ranked.sort_by(|a, b| scores[*a].total_cmp(&scores[*b]));
+        ranked
+    }
+
+    /// 重新检查未确认链 ID 的节点，再同时读取可用节点的最新区块并更新落后的区块数，返回其中最大的区块
+    pub async fn head(&self) -> Result<u64> {
+        self.verify().await;
+        let verified = self.checked(ChainCheck::Verified);
+        let results = self
+            .query_all(&verified, "eth_blockNumber")
+            .await
+            .into_iter()
+            .zip(verified)
+            .map(|(result, index)| {
+                let head = helpers::decode::<U64>(result?)?.as_u64();
+                self.inner.endpoints[index].health.lock().unwrap().head = head;
+                Ok(head)
+            })
+            .collect::<Vec<Result<u64>>>();
+        let head = results
+            .iter()
+            .filter_map(|result| result.as_ref().ok().copied())
+            .max();
+        match head {
+            Some(head) => Ok(head),
+            None => Err(results.into_iter().find_map(Result::err).unwrap()),
+        }
+    }
+
+    /// 检查所有节点的链 ID 是否一致，无法连接的节点记录警告并在确认链 ID 之前不再使用
+    pub async fn check_chain_id(&self) -> Result<U256> {
+        let mut chain_id: Option<(usize, U256)> = None;
+        let mut problems = vec![];
+        let mut unreachable = vec![];
+        let mut last = None;
+        let all: Vec<usize> = (0..self.len()).collect();
+        let results = self.query_all(&all, "eth_chainId").await;
+        for (index, result) in results.into_iter().enumerate() {
+            let result = result.and_then(|value| Ok(helpers::decode::<U256>(value)?));
+            match (result, chain_id) {
+                (Ok(id), None) => chain_id = Some((index, id)),
+                (Ok(id), Some((first, expected))) if id != expected => problems.push(format!(
+                    "rpc endpoint #{} is on chain {} but #{} is on chain {}",
+                    index, id, first, expected
+                )),
+                (Ok(_), Some(_)) => {}
+                (Err(err), _) => {
+                    log::warn!("rpc endpoint #{} chain id not checked - {}", index, err);
+                    unreachable.push(index);
+                    last = Some(err);
+                }
+            }
+        }
+        if !problems.is_empty() {
+            return Err(Error::invalid_param(&problems.join(", ")));
+        }
+        match (chain_id, last) {
+            (Some((_, id)), _) => {
+                for (index, endpoint) in self.inner.endpoints.iter().enumerate() {
+                    *endpoint.chain.lock().unwrap() = if unreachable.contains(&index) {
+                        ChainCheck::Unreachable
+                    } else {
+                        ChainCheck::Verified
+                    };
+                }
+                *self.inner.chain_id.lock().unwrap() = Some(id);
+                Ok(id)
+            }
+            (None, Some(err)) => Err(err.context("no rpc endpoint reachable to check chain id")),
+            (None, None) => Err(Error::invalid_param("requires at least one rpc uri")),
+        }
+    }
+
+    /// 重新检查启动时无法连接的节点，链 ID 一致时开始使用
+    async fn verify(&self) {
+        let expected = match *self.inner.chain_id.lock().unwrap() {
+            Some(id) => id,
+            None => return,
+        };
+        let unreachable = self.checked(ChainCheck::Unreachable);
+        if unreachable.is_empty() {
+            return;
+        }
+        let results = self.query_all(&unreachable, "eth_chainId").await;
+        for (result, index) in results.into_iter().zip(unreachable) {
+            let check = match result.and_then(|value| Ok(helpers::decode::<U256>(value)?)) {
+                Ok(id) if id == expected => {
+                    log::info!("rpc endpoint #{} is on chain {}, now in use", index, id);
+                    ChainCheck::Verified
+                }
+                Ok(id) => {
+                    log::error!(
+                        "rpc endpoint #{} is on chain {} but the others are on chain {}, not used",
+                        index,
+                        id,
+                        expected
+                    );
+                    ChainCheck::Mismatched
+                }
+                Err(err) => {
+                    log::debug!("rpc endpoint #{} chain id not checked - {}", index, err);
+                    ChainCheck::Unreachable
+                }
+            };
+            *self.inner.endpoints[index].chain.lock().unwrap() = check;
+        }
+    }
+
+    /// 向 `indexes` 中的每个节点发送同一个没有参数的请求
+    async fn query_all(&self, indexes: &[usize], method: &str) -> Vec<Result<Value>> {
+        join_all(indexes.iter().map(|&index| async move {
+            let (id, call) = self.prepare(method, vec![]);
+            Ok(self.send_to(index, id, call).await?)
+        }))
+        .await
+    }
+
+    /// 按健康分数依次尝试，不可重试的错误（如合约回滚）直接返回
+    async fn failover<T, F, Fut>(&self, send: F) -> web3::Result<T>
+    where
+        F: Fn(usize) -> Fut,
+        Fut: Future<Output = web3::Result<T>>,
+    {
+        let mut last = None;
+        for index in self.ranked() {
+            let err = match send(index).await {
+                Ok(value) => return Ok(value),
+                Err(err) => err,
+            };
+            let error = Error::from(err.clone());
+            if !error.is_retryable() {
+                return Err(err);
+            }
+            log::warn!("rpc endpoint #{} failed, fail over - {}", index, error);
+            last = Some(err);
+        }
+        Err(last.unwrap_or(web3::Error::Unreachable))
+    }
+
+    async fn send_to(&self, index: usize, id: RequestId, call: Call) -> web3::Result<Value> {
+        let endpoint = &self.inner.endpoints[index];
+        endpoint.acquire(&[&call]).await;
+        endpoint.track(endpoint.http.send(id, call)).await
+    }
+
+    /// 批量请求整体发送到同一个节点，限流按其中每个方法的权重计算
+    async fn send_batch_to(
+        &self,
+        index: usize,
+        requests: Vec<(RequestId, Call)>,
+    ) -> web3::Result<Vec<web3::Result<Value>>> {
+        let endpoint = &self.inner.endpoints[index];
+        let calls: Vec<&Call> = requests.iter().map(|(_, call)| call).collect();
+        endpoint.acquire(&calls).await;
+        endpoint.track(endpoint.http.send_batch(requests)).await
+    }
+}
+
+impl Endpoint {
+    async fn acquire(&self, calls: &[&Call]) {
+        if let Some(limiter) = &self.limiter {
+            for call in calls {
+                if let Call::MethodCall(call) = call {
+                    limiter.acquire(&call.method).await;
+                }
+            }
+        }
+    }
+
+    /// 记录请求的延迟与是否失败，并隐藏错误中的节点地址
+    async fn track<T, Fut>(&self, request: Fut) -> web3::Result<T>
+    where
+        Fut: Future<Output = web3::Result<T>>,
+    {
+        let start = Instant::now();
+        let result = request.await.map_err(|err| redact(err, &self.uri));
+        let failed = match &result {
+            Ok(_) => false,
+            // 节点有响应的错误不影响健康分数
+            Err(err) => Error::from(err.clone()).is_retryable(),
+        };
+        self.health.lock().unwrap().record(start.elapsed(), failed);
+        result
+    }
+}
+
+/// 隐藏 transport 错误中的节点地址
+fn redact(err: web3::Error, uri: &Secret) -> web3::Error {
+    match err {
+        web3::Error::Transport(msg) => web3::Error::Transport(uri.redact_str(&msg)),
+        web3::Error::InvalidResponse(msg) => web3::Error::InvalidResponse(uri.redact_str(&msg)),
+        web3::Error::Decoder(msg) => web3::Error::Decoder(uri.redact_str(&msg)),
+        err => err,
+    }
+}
+
+impl Debug for RpcPool {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let uris: Vec<&Secret> = self.inner.endpoints.iter().map(|e| &e.uri).collect();
+        f.debug_struct("RpcPool").field("endpoints", &uris).finish()
+    }
+}
+
+impl Transport for RpcPool {
+    type Out = BoxFuture<'static, web3::Result<Value>>;
+
+    fn prepare(&self, method: &str, params: Vec<Value>) -> (RequestId, Call) {
+        let id = self.inner.next_id.fetch_add(1, Ordering::AcqRel);
+        (id, helpers::build_request(id, method, params))
+    }
+
+    fn send(&self, id: RequestId, call: Call) -> Self::Out {
+        let pool = self.clone();
+        Box::pin(async move {
+            pool.failover(|index| pool.send_to(index, id, call.clone()))
+                .await
+        })
+    }
+}
+
+impl BatchTransport for RpcPool {
+    type Batch = BoxFuture<'static, web3::Result<Vec<web3::Result<Value>>>>;
+
+    fn send_batch<T>(&self, requests: T) -> Self::Batch
+    where
+        T: IntoIterator<Item = (RequestId, Call)>,
+    {
+        let pool = self.clone();
+        let requests: Vec<(RequestId, Call)> = requests.into_iter().collect();
+        Box::pin(async move {
+            pool.failover(|index| pool.send_batch_to(index, requests.clone()))
+                .await
+        })
+    }
+}
+
+/// 返回固定最新区块与链 ID 的节点，`eth_call` 总是回滚，`calls` 记录请求数
+#[cfg(test)]
+async fn node(head: u64, chain_id: u64, calls: Arc<AtomicUsize>) -> Secret {
+    use super::mock::{quantity, serve, Reply};
+    let uri = serve(move |method, _| {
+        calls.fetch_add(1, Ordering::SeqCst);
+        async move {
+            match method.as_str() {
+                "eth_blockNumber" => Reply::Result(quantity(head)),
+                "eth_chainId" => Reply::Result(quantity(chain_id)),
+                "eth_call" => Reply::Error(3, "execution reverted".to_owned()),
+                _ => Reply::Status(503),
+            }
+        }
+    })
+    .await;
+    Secret::new(&uri)
+}
+
+#[tokio::test]
+async fn test_failover() {
+    use super::mock::{serve, Reply};
+
+    let down = Secret::new(&serve(|_, _| async { Reply::Status(503) }).await);
+    let calls = Arc::new(AtomicUsize::new(0));
+    let pool = RpcPool::new(&[down, node(16, 1, calls.clone()).await], None).unwrap();
+    let web3 = web3::Web3::new(pool.clone());
+    assert_eq!(16, web3.eth().block_number().await.unwrap().as_u64());
+    assert_eq!(vec![1, 0], pool.ranked());
+    assert_eq!(16, web3.eth().block_number().await.unwrap().as_u64());
+
+    // 合约回滚不是节点的问题，不切换节点
+    let calls_before = calls.load(Ordering::SeqCst);
+    let err = Error::from(web3.eth().call(Default::default(), None).await.unwrap_err());
+    assert!(err.is_reverted());
+    assert_eq!(calls_before + 1, calls.load(Ordering::SeqCst));
+
+    let refused = Secret::new("http://127.0.0.1:1");
+    let pool = RpcPool::new(&[refused], None).unwrap();
+    let err = Error::from(
+        web3::Web3::new(pool)
+            .eth()
+            .block_number()
+            .await
+            .unwrap_err(),
+    );
+    assert!(err.is_transport_err());
+    assert!(!err.to_string().contains("127.0.0.1:1"));
+}
+
+#[tokio::test]
+async fn test_batch() {
+    use super::mock::{serve, Reply};
+
+    let down = Secret::new(&serve(|_, _| async { Reply::Status(503) }).await);
+    let calls = Arc::new(AtomicUsize::new(0));
+    let pool = RpcPool::new(&[down, node(16, 5, calls.clone()).await], None).unwrap();
+    let requests: Vec<(RequestId, Call)> = ["eth_blockNumber", "eth_chainId", "eth_call"]
+        .into_iter()
+        .map(|method| pool.prepare(method, vec![]))
+        .collect();
+    let results = pool.send_batch(requests).await.unwrap();
+    assert_eq!(3, calls.load(Ordering::SeqCst));
+    assert_eq!(vec![1, 0], pool.ranked());
+
+    let values: Vec<u64> = results[..2]
+        .iter()
+        .map(|result| {
+            helpers::decode::<U64>(result.clone().unwrap())
+                .unwrap()
+                .as_u64()
+        })
+        .collect();
+    assert_eq!(vec![16, 5], values);
+    assert!(Error::from(results[2].clone().unwrap_err()).is_reverted());
+}
+
+#[tokio::test]
+async fn test_head_lag() {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let pool = RpcPool::new(
+        &[
+            node(90, 1, calls.clone()).await,
+            node(100, 1, calls.clone()).await,
+        ],
+        None,
+    )
+    .unwrap();
+    assert_eq!(100, pool.head().await.unwrap());
+    assert_eq!(vec![1, 0], pool.ranked());
+}
+
+#[tokio::test]
+async fn test_check_chain_id() {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let mainnet = node(100, 1, calls.clone()).await;
+    let goerli = node(100, 5, calls.clone()).await;
+    let refused = Secret::new("http://127.0.0.1:1");
+
+    let pool = RpcPool::new(&[mainnet.clone(), refused.clone()], None).unwrap();
+    assert_eq!(U256::from(1), pool.check_chain_id().await.unwrap());
+
+    let pool = RpcPool::new(&[mainnet, refused.clone(), goerli], None).unwrap();
+    let err = pool.check_chain_id().await.unwrap_err();
+    assert!(err.is_invalid_err());
+    assert_eq!(
+        "rpc endpoint #2 is on chain 5 but #0 is on chain 1",
+        err.get_msg()
+    );
+
+    let err = RpcPool::new(&[refused], None)
+        .unwrap()
+        .check_chain_id()
+        .await
+        .unwrap_err();
+    assert!(err.is_retryable());
+}
+
+#[tokio::test]
+async fn test_unverified_endpoint() {
+    use super::mock::{quantity, serve, Reply};
+    use std::sync::atomic::AtomicBool;
+
+    // 启动时无法连接，之后恢复，`chain_id` 为恢复后返回的链 ID
+    async fn flaky(up: Arc<AtomicBool>, chain_id: u64) -> Secret {
+        let uri = serve(move |method, _| {
+            let up = up.load(Ordering::SeqCst);
+            async move {
+                match method.as_str() {
+                    _ if !up => Reply::Status(503),
+                    "eth_blockNumber" => Reply::Result(quantity(200)),
+                    "eth_chainId" => Reply::Result(quantity(chain_id)),
+                    _ => Reply::Status(503),
+                }
+            }
+        })
+        .await;
+        Secret::new(&uri)
+    }
+
+    let calls = Arc::new(AtomicUsize::new(0));
+    let up = Arc::new(AtomicBool::new(false));
+    let pool = RpcPool::new(
+        &[
+            node(100, 1, calls.clone()).await,
+            flaky(up.clone(), 5).await,
+            flaky(up.clone(), 1).await,
+        ],
+        None,
+    )
+    .unwrap();
+    assert_eq!(vec![0, 1, 2], pool.ranked());
+    assert_eq!(U256::from(1), pool.check_chain_id().await.unwrap());
+    assert_eq!(vec![0], pool.ranked());
+
+    // 仍然无法连接
+    assert_eq!(100, pool.head().await.unwrap());
+    assert_eq!(vec![0], pool.ranked());
+
+    // 恢复后链 ID 一致的节点重新使用，链 ID 不一致的节点不参与排序，也不影响最新区块
+    up.store(true, Ordering::SeqCst);
+    assert_eq!(200, pool.head().await.unwrap());
+    assert_eq!(vec![2, 0], pool.ranked());
+    assert_eq!(200, pool.head().await.unwrap());
+    assert_eq!(vec![2, 0], pool.ranked());
+    assert_eq!(vec![1], pool.checked(ChainCheck::Mismatched));
+}
@@ -0,0 +1,421 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use web3::ethabi::{self, ParamType, Token};
+
+use crate::config::{ConfigErrors, Section};
+use crate::decode::load_contract;
+use crate::value::Bytes;
+use crate::{event::Event, Config, DataType, Error, Result, Value};
+
+/// 事件字段定义
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldSchema {
+    /// 字段类型
+    pub data_type: DataType,
+    /// 是否必须存在
+    pub required: bool,
+    /// 字段缺失时使用的默认值
+    pub default: Option<Value>,
+}
+
+impl FieldSchema {
+    pub fn new(data_type: DataType) -> Self {
+        Self {
+            data_type,
+            required: true,
+            default: None,
+        }
+    }
+
+    pub fn optional(mut self) -> Self {
+        self.required = false;
+        self
+    }
+
+    pub fn with_default(mut self, default: Value) -> Self {
+        self.default = Some(default);
+        self
+    }
+}
+
+/// 事件结构定义（字段名 -> 字段定义）
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct EventSchema {
+    fields: BTreeMap<String, FieldSchema>,
+}
+
+impl EventSchema {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn field<N: Into<String>>(mut self, name: N, field: FieldSchema) -> Self {
+        self.fields.insert(name.into(), field);
+        self
+    }
+
+    pub fn get_field(&self, name: &str) -> Option<&FieldSchema> {
+        self.fields.get(name)
+    }
+
+    pub fn fields(&self) -> impl Iterator<Item = (&String, &FieldSchema)> {
+        self.fields.iter()
+    }
+
+    /// 从配置中读取事件结构，`key` 下的每一项为一个字段：
+    ///
+    /// ```toml
+    /// [processor.validate.fields]
+    /// from = "string"
+    /// token_id = { type = "integer", required = true }
+    /// memo = { type = "string", default = "" }
+    /// ```
+    pub fn from_config<C: Config>(config: &C, key: &str) -> Result<Self> {
+        let mut errors = ConfigErrors::new();
+        let mut section = Section::new(config, key, &mut errors);
+        let schema = Self::from_section(&mut section);
+        section.finish();
+        errors.into_result(schema)
+    }
+
+    /// 从配置表中读取事件结构，问题记录在 `section` 中
+    pub fn from_section<C: Config>(section: &mut Section<C>) -> Option<Self> {
+        if !section.exists() {
+            section.error("", "missing fields table");
+            return None;
+        }
+        let mut schema = Self::new();
+        for name in section.keys() {
+            let mut field_section = section.section(&name);
+            let field = if field_section.exists() {
+                let field = Self::field_from_section(&mut field_section);
+                field_section.finish();
+                field
+            } else {
+                let data_type = section.required::<String>(&name);
+                data_type.and_then(|data_type| {
+                    section
+                        .check(&name, data_type.parse())
+                        .map(FieldSchema::new)
+                })
+            };
+            if let Some(field) = field {
+                schema.fields.insert(name, field);
+            }
+        }
+        Some(schema)
+    }
+
+    fn field_from_section<C: Config>(section: &mut Section<C>) -> Option<FieldSchema> {
+        let data_type = section.required::<String>("type")?;
+        let data_type: DataType = section.check("type", data_type.parse())?;
+        let mut field = FieldSchema::new(data_type);
+        field.required = section.get_or("required", true);
+        if let Some(default) = section.value("default") {
+            let default = default.cast(data_type);
+            field.default = Some(section.check("default", default)?);
+        }
+        Some(field)
+    }
+
+    /// 根据 ABI 事件定义生成事件结构，所有参数都为必须字段
+    pub fn from_abi_event(event: &ethabi::Event) -> Self {
+        let mut schema = Self::new();
+        for param in &event.inputs {
+            schema = schema.field(&param.name, FieldSchema::new(param_type(&param.kind)));
+        }
+        schema
+    }
+
+    /// 从 ABI JSON 文件中读取指定事件的结构
+    pub fn from_abi_path<P: AsRef<Path>>(path: P, event: &str) -> Result<Self> {
+        let contract = load_contract(&std::fs::read(path)?)?;
+        Ok(Self::from_abi_event(contract.event(event)?))
+    }
+
+    /// 校验事件，返回所有不符合结构定义的问题
+    pub fn check(&self, event: &Event) -> Vec<String> {
+        let mut problems = vec![];
+        for (name, field) in &self.fields {
+            match event.get(name) {
+                None | Some(Value::Nil) if field.default.is_some() => {}
+                None if field.required => problems.push(format!("missing field {}", name)),
+                Some(Value::Nil) if field.required => {
+                    problems.push(format!("field {} must not be Nil", name))
+                }
+                Some(value) if !value.is_nil() && value.get_type() != field.data_type => problems
+                    .push(format!(
+                        "field {} expects {} but got {}",
+                        name,
+                        field.data_type,
+                        value.get_type()
+                    )),
+                _ => {}
+            }
+        }
+        problems
+    }
+
+    /// 校验事件，不符合时返回包含所有问题的 `Error::invalid_data`
+    pub fn validate(&self, event: &Event) -> Result<()> {
+        let problems = self.check(event);
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::invalid_data(&problems.join("; ")))
+        }
+    }
+
+    /// 为缺少或为 `Nil` 的字段补齐默认值，不转换类型
+    pub fn fill_defaults(&self, mut event: Event) -> Event {
+        for (name, field) in &self.fields {
+            if let Some(default) = &field.default {
+                if matches!(event.get(name), None | Some(Value::Nil)) {
+                    event.insert(name.clone(), default.clone());
+                }
+            }
+        }
+        event
+    }
+
+    /// 补齐默认值并将字段转换为定义的类型，可选字段显式的 `Nil` 保持不变
+    pub fn coerce(&self, mut event: Event) -> Result<Event> {
+        let mut problems = vec![];
+        for (name, field) in &self.fields {
+            let value = match (event.remove(name), &field.default) {
+                (None | Some(Value::Nil), Some(default)) => default.clone(),
+                (None, None) => {
+                    if field.required {
+                        problems.push(format!("missing field {}", name));
+                    }
+                    continue;
+                }
+                (Some(Value::Nil), None) => {
+                    if field.required {
+                        problems.push(format!("missing field {}", name));
+                    }
+                    Value::Nil
+                }
+                (Some(value), _) => match value.cast(field.data_type) {
+                    Ok(value) => value,
+                    Err(err) => {
+                        problems.push(format!("field {}: {}", name, err.get_msg()));
+                        continue;
+                    }
+                },
+            };
+            event.insert(name.clone(), value);
+        }
+
+        if problems.is_empty() {
+            Ok(event)
+        } else {
+            Err(Error::invalid_data(&problems.join("; ")))
+        }
+    }
+}
+
+/// ABI 参数类型对应的数据类型，超出 i64 范围的整数使用十进制字符串
+pub fn param_type(kind: &ParamType) -> DataType {
+    match kind {
+        ParamType::Address | ParamType::String => DataType::String,
+        ParamType::Bytes | ParamType::FixedBytes(_) => DataType::Bytes,
+        ParamType::Int(size) if *size <= 64 => DataType::Integer,
+        ParamType::Uint(size) if *size < 64 => DataType::Integer,
+        ParamType::Int(_) | ParamType::Uint(_) => DataType::String,
+        ParamType::Bool => DataType::Boolean,
+        ParamType::Array(_) | ParamType::FixedArray(_, _) | ParamType::Tuple(_) => DataType::Array,
+    }
+}
+
+/// ABI 参数值转为 `param_type` 对应类型的值，超出 i64 范围的整数使用十进制字符串
+pub fn token_value(kind: &ParamType, token: Token) -> Value {
+    match (kind, token) {
+        (_, Token::Address(address)) => Value::from(format!("{:?}", address)),
+        (_, Token::String(text)) => Value::from(text),
+        (_, Token::Bytes(bytes)) | (_, Token::FixedBytes(bytes)) => Value::Bytes(Bytes(bytes)),
+        (_, Token::Bool(flag)) => Value::from(flag),
+        // 有符号整数以补码编码，低 64 位即对应的 i64
+        (ParamType::Int(size), Token::Int(value)) if *size <= 64 => {
+            Value::Integer(value.low_u64() as i64)
+        }
+        (_, Token::Int(value)) if value.bit(255) => {
+            Value::from(format!("-{}", (!value).overflowing_add(1.into()).0))
+        }
+        (ParamType::Uint(size), Token::Uint(value)) if *size < 64 => {
+            Value::Integer(value.low_u64() as i64)
+        }
+        (_, Token::Int(value)) | (_, Token::Uint(value)) => Value::from(value.to_string()),
+        (ParamType::Array(kind), Token::Array(tokens))
+        | (ParamType::FixedArray(kind, _), Token::FixedArray(tokens)) => Value::Array(
+            tokens
+                .into_iter()
+                .map(|token| token_value(kind, token))
+                .collect(),
+        ),
+        (ParamType::Tuple(kinds), Token::Tuple(tokens)) => Value::Array(
+            kinds
+                .iter()
+                .zip(tokens)
+                .map(|(kind, token)| token_value(kind, token))
+                .collect(),
+        ),
+        (_, token) => Value::from(token.to_string()),
+    }
+}
+
+#[cfg(test)]
+fn transfer_schema() -> EventSchema {
+    EventSchema::new()
+        .field("from", FieldSchema::new(DataType::String))
+        .field("token_id", FieldSchema::new(DataType::Integer))
+        .field(
+            "memo",
+            FieldSchema::new(DataType::String).with_default(Value::from("")),
+        )
+        .field("removed", FieldSchema::new(DataType::Boolean).optional())
+}
+
+#[test]
+fn test_from_config() {
+    let config = crate::config::TomlConfig::from_string(
+        r#"
+        [processor.validate.fields]
+        from = "string"
+        token_id = { type = "integer", required = true }
+        memo = { type = "string", default = "" }
+        removed = { type = "boolean", required = false }
+        "#,
+    )
+    .unwrap();
+    let schema = EventSchema::from_config(&config, "processor.validate.fields").unwrap();
+    assert_eq!(transfer_schema(), schema);
+
+    let config = crate::config::TomlConfig::from_string(
+        r#"
+        [fields]
+        token_id = { type = "integer", default = "ten" }
+        from = "address"
+        to = { type = "string", required = "no", nullable = true }
+        "#,
+    )
+    .unwrap();
+    let err = EventSchema::from_config(&config, "fields").unwrap_err();
+    assert_eq!(
+        "found 4 problem(s) in config:\n  \
+         fields.from: failed to parse str address for DataType\n  \
+         fields.to.required: expects Boolean but got String\n  \
+         fields.to.nullable: unknown key\n  \
+         fields.token_id.default: failed to cast String(\"ten\") to Integer - invalid digit found in string",
+        err.get_msg()
+    );
+    assert!(EventSchema::from_config(&config, "processor").is_err());
+}
+
+#[test]
+fn test_from_abi() {
+    let schema = EventSchema::from_abi_path("abi/AuthToken.json", "Transfer").unwrap();
+    let expect = EventSchema::new()
+        .field("from", FieldSchema::new(DataType::String))
+        .field("to", FieldSchema::new(DataType::String))
+        .field("tokenId", FieldSchema::new(DataType::String));
+    assert_eq!(expect, schema);
+
+    let schema = EventSchema::from_abi_path("abi/AuthToken.json", "ApprovalForAll").unwrap();
+    assert_eq!(
+        Some(DataType::Boolean),
+        schema.get_field("approved").map(|field| field.data_type)
+    );
+    assert!(EventSchema::from_abi_path("abi/AuthToken.json", "Mint").is_err());
+}
+
+#[test]
+fn test_validate() {
+    let schema = transfer_schema();
+    let mut event = Event::new();
+    event.insert("from".to_owned(), Value::from("0x00"));
+    event.insert("token_id".to_owned(), Value::from(10));
+    assert!(schema.validate(&event).is_ok());
+
+    event.insert("token_id".to_owned(), Value::from("10"));
+    event.insert("removed".to_owned(), Value::Nil);
+    event.remove("from");
+    let err = schema.validate(&event).unwrap_err();
+    assert_eq!(
+        "missing field from; field token_id expects Integer but got String",
+        err.get_msg()
+    );
+}
+
+#[test]
+fn test_fill_defaults() {
+    let schema = transfer_schema();
+    let mut event = Event::new();
+    event.insert("token_id".to_owned(), Value::from("10"));
+    event.insert("removed".to_owned(), Value::Nil);
+    let event = schema.fill_defaults(event);
+    assert_eq!(Some(&Value::from("")), event.get("memo"));
+    assert_eq!(Some(&Value::from("10")), event.get("token_id"));
+    assert_eq!(Some(&Value::Nil), event.get("removed"));
+    assert_eq!(None, event.get("from"));
+}
+
+#[test]
+fn test_coerce() {
+    let schema = transfer_schema();
+    let mut event = Event::new();
+    event.insert("from".to_owned(), Value::from("0x00"));
+    event.insert("token_id".to_owned(), Value::from("10"));
+    event.insert("extra".to_owned(), Value::from(1));
+
+    let event = schema.coerce(event).unwrap();
+    assert_eq!(Some(&Value::Integer(10)), event.get("token_id"));
+    assert_eq!(Some(&Value::from("")), event.get("memo"));
+    assert_eq!(Some(&Value::from(1)), event.get("extra"));
+    assert_eq!(None, event.get("removed"));
+    assert!(schema.validate(&event).is_ok());
+
+    // 可选字段显式的 Nil 保留
+    let mut event = event;
+    event.insert("removed".to_owned(), Value::Nil);
+    let event = schema.coerce(event).unwrap();
+    assert_eq!(Some(&Value::Nil), event.get("removed"));
+
+    let mut event = Event::new();
+    event.insert("token_id".to_owned(), Value::from("ten"));
+    let err = schema.coerce(event).unwrap_err();
+    assert!(err
+        .get_msg()
+        .starts_with("missing field from; field token_id:"));
+}
+
+#[test]
+fn test_token_value() {
+    use web3::types::{H160, U256};
+
+    let address = H160::from_low_u64_be(1);
+    assert_eq!(
+        Value::from("0x0000000000000000000000000000000000000001"),
+        token_value(&ParamType::Address, Token::Address(address))
+    );
+    assert_eq!(
+        Value::Integer(-2),
+        token_value(&ParamType::Int(32), Token::Int(U256::MAX - 1))
+    );
+    assert_eq!(
+        Value::from("-2"),
+        token_value(&ParamType::Int(256), Token::Int(U256::MAX - 1))
+    );
+    assert_eq!(
+        Value::from("7"),
+        token_value(&ParamType::Uint(256), Token::Uint(7.into()))
+    );
+    let kind = ParamType::Array(Box::new(ParamType::Uint(8)));
+    let tokens = Token::Array(vec![Token::Uint(1.into()), Token::Uint(2.into())]);
+    assert_eq!(
+        Value::Array(vec![Value::Integer(1), Value::Integer(2)]),
+        token_value(&kind, tokens)
+    );
+    assert_eq!(DataType::Array, param_type(&kind));
+}
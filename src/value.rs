@@ -1,12 +1,16 @@
 use std::{convert::TryFrom, fmt::Display, str::FromStr};
 
-use crate::{DataType, error::Error};
+use crate::{error::Error, DataType};
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Bytes(pub Vec<u8>);
 
 /// 所支持的值类型
+///
+/// 开启 `serde` feature 后以外部标签形式序列化，如 `{"Integer":10}`，保证各格式下可无损往返
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Value {
     /// 字符串类型
     String(String),
@@ -53,6 +57,25 @@ macro_rules! impl_try_from {
         }
     };
 }
+macro_rules! impl_try_from_integer {
+    ($T:ty , $type_s: expr) => {
+        impl TryFrom<Value> for $T {
+            type Error = Error;
+            fn try_from(value: Value) -> Result<Self, Self::Error> {
+                if let Value::Integer(val) = value {
+                    <$T>::try_from(val).map_err(|_| {
+                        Error::invalid_type(&format!("{} is out of range for {}", val, $type_s))
+                    })
+                } else {
+                    Err(Error::invalid_type(&format!(
+                        "failed to parse {} for {:?}",
+                        $type_s, value
+                    )))
+                }
+            }
+        }
+    };
+}
 
 impl_into_value!(Integer: i64);
 impl_into_value!(Integer: i32);
@@ -67,20 +90,22 @@ impl_into_value!(String: &str);
 impl_into_value!(Boolean: bool);
 impl_into_value!(Bytes: Bytes);
 
-impl_try_from!(Integer: i64, "i64");
-impl_try_from!(Integer: i32, "i32");
-impl_try_from!(Integer: i16, "i16");
-impl_try_from!(Integer: i8, "i8");
-impl_try_from!(Integer: u32, "u32");
-impl_try_from!(Integer: u16, "u16");
-impl_try_from!(Integer: u8, "u8");
+impl_try_from_integer!(i64, "i64");
+impl_try_from_integer!(i32, "i32");
+impl_try_from_integer!(i16, "i16");
+impl_try_from_integer!(i8, "i8");
+impl_try_from_integer!(u32, "u32");
+impl_try_from_integer!(u16, "u16");
+impl_try_from_integer!(u8, "u8");
+impl_try_from_integer!(u64, "u64");
+impl_try_from_integer!(usize, "usize");
 impl_try_from!(Number: f64, "f64");
 impl_try_from!(Number: f32, "f32");
 impl_try_from!(Boolean: bool, "bool");
 impl_try_from!(Bytes: Bytes, "bytes");
 
-impl Into<Value> for () {
-    fn into(self) -> Value {
+impl From<()> for Value {
+    fn from(_: ()) -> Self {
         Value::Nil
     }
 }
@@ -113,7 +138,24 @@ impl<T: Into<Value>> From<Vec<T>> for Value {
         for val in array {
             src.push(val.into());
         }
-        return Value::Array(src);
+        Value::Array(src)
+    }
+}
+
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(val: Option<T>) -> Self {
+        val.map(Into::into).unwrap_or(Value::Nil)
+    }
+}
+
+impl<T: TryFrom<Value, Error = Error>> TryFrom<Value> for Option<T> {
+    type Error = Error;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::Nil => Ok(None),
+            value => T::try_from(value).map(Some),
+        }
     }
 }
 
@@ -121,7 +163,7 @@ impl TryFrom<Value> for Vec<Value> {
     type Error = Error;
 
     fn try_from(value: Value) -> Result<Self, Self::Error> {
-        return if let Value::Array(array) = value {
+        if let Value::Array(array) = value {
             let mut src = vec![];
             for val in array {
                 src.push(val);
@@ -132,7 +174,7 @@ impl TryFrom<Value> for Vec<Value> {
                 "failed to parse array for {:?}",
                 value
             )))
-        };
+        }
     }
 }
 
@@ -140,7 +182,7 @@ impl<T: TryFrom<Value, Error = Error>> TryFrom<Value> for Vec<T> {
     type Error = Error;
 
     fn try_from(value: Value) -> Result<Self, Self::Error> {
-        return if let Value::Array(array) = value {
+        if let Value::Array(array) = value {
             let mut src = vec![];
             for val in array {
                 src.push(T::try_from(val)?);
@@ -151,39 +193,221 @@ impl<T: TryFrom<Value, Error = Error>> TryFrom<Value> for Vec<T> {
                 "failed to parse array for {:?}",
                 value
             )))
-        };
+        }
     }
 }
 
+/// 严格解析值的字面量
+///
+/// 支持的语法：
+/// - `nil`/`null`：空值
+/// - `true`/`false`：Boolean
+/// - `10`、`-10`：Integer
+/// - `10.01`、`1e-3`、`inf`、`nan`：Number
+/// - `"a\"b"`、`'ab'`：带转义的字符串
+/// - `0x0912`：字节数组
+/// - `[1, "a", [nil]]`：数组
+///
+/// 无法识别的字面量返回 `Error::invalid_type`，宽松解析请使用 [`Value::parse_lenient`]
 impl FromStr for Value {
     type Err = Error;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        if s.contains("'") {
-            return Ok(Value::String(s.replace("'", "").to_owned()));
+        let mut parser = LiteralParser::new(s);
+        let value = parser.parse_value()?;
+        parser.skip_whitespace();
+        if parser.pos < parser.src.len() {
+            return Err(parser.error("unexpected trailing characters"));
         }
+        Ok(value)
+    }
+}
 
-        let value = if s.contains("false") || s.contains("true") {
-            s.parse::<bool>()
-                .map(|v| Value::Boolean(v))
-                .unwrap_or(Value::String(s.to_owned()))
-        } else if s.contains(".") {
-            s.parse::<f64>()
-                .map(|v| Value::Number(v))
-                .unwrap_or(Value::String(s.to_owned()))
-        } else if s == "null" || s == "NULL" || s == "Null" {
-            Value::Nil
-        } else if s == "nil" || s == "Nil" {
-            Value::Nil
-        } else {
-            s.parse::<i64>()
-                .map(|v| Value::Integer(v))
-                .unwrap_or(Value::String(s.to_owned()))
+struct LiteralParser<'a> {
+    src: &'a str,
+    pos: usize,
+}
+
+impl<'a> LiteralParser<'a> {
+    fn new(src: &'a str) -> Self {
+        Self { src, pos: 0 }
+    }
+
+    fn error(&self, msg: &str) -> Error {
+        Error::invalid_type(&format!(
+            "failed to parse value literal {:?} at {} - {}",
+            self.src, self.pos, msg
+        ))
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.src[self.pos..].chars().next()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(c) = self.peek() {
+            if !c.is_whitespace() {
+                break;
+            }
+            self.pos += c.len_utf8();
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<Value, Error> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('"') | Some('\'') => self.parse_string(),
+            Some('[') => self.parse_array(),
+            Some(_) => self.parse_atom(),
+            None => Err(self.error("unexpected end of input")),
+        }
+    }
+
+    fn parse_string(&mut self) -> Result<Value, Error> {
+        let quote = self.next().unwrap_or('"');
+        let mut val = String::new();
+        loop {
+            match self.next() {
+                Some(c) if c == quote => return Ok(Value::String(val)),
+                Some('\\') => val.push(self.parse_escape()?),
+                Some(c) => val.push(c),
+                None => return Err(self.error("unterminated string")),
+            }
+        }
+    }
+
+    fn parse_escape(&mut self) -> Result<char, Error> {
+        let c = match self.next() {
+            Some('n') => '\n',
+            Some('r') => '\r',
+            Some('t') => '\t',
+            Some('0') => '\0',
+            Some('\\') => '\\',
+            Some('"') => '"',
+            Some('\'') => '\'',
+            Some('u') => {
+                if self.next() != Some('{') {
+                    return Err(self.error("expected '{' after \\u"));
+                }
+                let start = self.pos;
+                while matches!(self.peek(), Some(c) if c.is_ascii_hexdigit()) {
+                    self.pos += 1;
+                }
+                let hex = &self.src[start..self.pos];
+                if self.next() != Some('}') {
+                    return Err(self.error("expected '}' after unicode escape"));
+                }
+                u32::from_str_radix(hex, 16)
+                    .ok()
+                    .and_then(char::from_u32)
+                    .ok_or_else(|| self.error("invalid unicode escape"))?
+            }
+            _ => return Err(self.error("invalid escape sequence")),
         };
+        Ok(c)
+    }
 
+    fn parse_array(&mut self) -> Result<Value, Error> {
+        self.next();
+        let mut array = vec![];
+        loop {
+            self.skip_whitespace();
+            if self.peek() == Some(']') {
+                self.next();
+                return Ok(Value::Array(array));
+            }
+            array.push(self.parse_value()?);
+            self.skip_whitespace();
+            match self.next() {
+                Some(',') => continue,
+                Some(']') => return Ok(Value::Array(array)),
+                _ => return Err(self.error("expected ',' or ']' in array")),
+            }
+        }
+    }
+
+    fn parse_atom(&mut self) -> Result<Value, Error> {
+        let start = self.pos;
+        while let Some(c) = self.peek() {
+            if c.is_whitespace() || c == ',' || c == ']' || c == '[' {
+                break;
+            }
+            self.pos += c.len_utf8();
+        }
+        let atom = &self.src[start..self.pos];
+
+        let value = match atom {
+            "nil" | "Nil" | "null" | "Null" | "NULL" => Value::Nil,
+            "true" => Value::Boolean(true),
+            "false" => Value::Boolean(false),
+            "inf" | "+inf" => Value::Number(f64::INFINITY),
+            "-inf" => Value::Number(f64::NEG_INFINITY),
+            "nan" => Value::Number(f64::NAN),
+            _ if atom.starts_with("0x") || atom.starts_with("0X") => {
+                Value::Bytes(parse_hex(&atom[2..]).map_err(|msg| self.error(&msg))?)
+            }
+            _ if is_integer_literal(atom) => atom
+                .parse::<i64>()
+                .map(Value::Integer)
+                .map_err(|err| self.error(&err.to_string()))?,
+            _ if is_number_literal(atom) => atom
+                .parse::<f64>()
+                .map(Value::Number)
+                .map_err(|err| self.error(&err.to_string()))?,
+            _ => return Err(self.error("unknown literal")),
+        };
         Ok(value)
     }
 }
 
+fn is_integer_literal(s: &str) -> bool {
+    let digits = s.strip_prefix(['-', '+']).unwrap_or(s);
+    !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit())
+}
+
+fn is_number_literal(s: &str) -> bool {
+    let s = s.strip_prefix(['-', '+']).unwrap_or(s);
+    let (mantissa, exponent) = match s.find(['e', 'E']) {
+        Some(index) => (&s[..index], Some(&s[index + 1..])),
+        None => (s, None),
+    };
+    let (int, frac) = match mantissa.find('.') {
+        Some(index) => (&mantissa[..index], &mantissa[index + 1..]),
+        None => (mantissa, ""),
+    };
+    let all_digits = |s: &str| s.bytes().all(|b| b.is_ascii_digit());
+    if int.is_empty() && frac.is_empty() || !all_digits(int) || !all_digits(frac) {
+        return false;
+    }
+    match exponent {
+        Some(exponent) => is_integer_literal(exponent),
+        None => true,
+    }
+}
+
+fn parse_hex(hex: &str) -> Result<Bytes, String> {
+    if !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(format!("invalid hex digits {:?}", hex));
+    }
+    if hex.len() % 2 == 1 {
+        return Err("hex bytes must have an even number of digits".to_owned());
+    }
+    // 只包含 ASCII 十六进制数字，按字节切分不会落在字符中间
+    hex.as_bytes()
+        .chunks(2)
+        .map(|pair| {
+            let pair = std::str::from_utf8(pair).expect("ascii hex digits");
+            u8::from_str_radix(pair, 16).map_err(|_| format!("invalid hex digits {:?}", pair))
+        })
+        .collect::<Result<Vec<u8>, String>>()
+        .map(Bytes)
+}
+
 impl Display for Value {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match &self {
@@ -212,50 +436,269 @@ impl Value {
     }
 
     pub fn is_nil(&self) -> bool {
-        if let Value::Nil = self {
-            return true;
+        matches!(self, Value::Nil)
+    }
+
+    /// 按照目标数据类型转换值，无法无损转换时返回 `Error::invalid_type`
+    ///
+    /// 转换规则：
+    /// - Integer <-> Number：Number 必须为整数且在 i64 范围内，Integer 必须能被 f64 精确表示
+    /// - Boolean <-> Integer：`true` 为 1，`false` 为 0，其他整数无法转换
+    /// - String -> Integer/Number/Boolean：按对应类型的文本格式解析
+    /// - Integer/Number/Boolean -> String：转为文本
+    /// - Bytes <-> String：使用 `0x` 开头的十六进制字符串
+    /// - 同类型直接返回，Nil 与 Array 只能转换为自身类型
+    pub fn cast(self, data_type: DataType) -> Result<Value, Error> {
+        if self.get_type() == data_type {
+            return Ok(self);
+        }
+        let value = match (&self, data_type) {
+            (Value::Integer(val), DataType::Number) => {
+                let number = *val as f64;
+                if number as i64 != *val || number == i64::MAX as f64 {
+                    return Err(self.cast_error(data_type, "loses precision"));
+                }
+                Value::Number(number)
+            }
+            (Value::Number(val), DataType::Integer) => {
+                if !val.is_finite() || val.fract() != 0.0 {
+                    return Err(self.cast_error(data_type, "is not an integer"));
+                }
+                if *val < i64::MIN as f64 || *val >= i64::MAX as f64 {
+                    return Err(self.cast_error(data_type, "is out of range"));
+                }
+                Value::Integer(*val as i64)
+            }
+            (Value::Boolean(val), DataType::Integer) => Value::Integer(*val as i64),
+            (Value::Integer(val), DataType::Boolean) => match val {
+                0 => Value::Boolean(false),
+                1 => Value::Boolean(true),
+                _ => return Err(self.cast_error(data_type, "is neither 0 nor 1")),
+            },
+            (Value::String(val), DataType::Integer) => val
+                .trim()
+                .parse::<i64>()
+                .map(Value::Integer)
+                .map_err(|err| self.cast_error(data_type, &err.to_string()))?,
+            (Value::String(val), DataType::Number) => {
+                let val = val.trim();
+                if !is_integer_literal(val) && !is_number_literal(val) {
+                    return Err(self.cast_error(data_type, "is not a number"));
+                }
+                val.parse::<f64>()
+                    .map(Value::Number)
+                    .map_err(|err| self.cast_error(data_type, &err.to_string()))?
+            }
+            (Value::String(val), DataType::Boolean) => val
+                .trim()
+                .parse::<bool>()
+                .map(Value::Boolean)
+                .map_err(|err| self.cast_error(data_type, &err.to_string()))?,
+            (Value::String(val), DataType::Bytes) => {
+                let hex = val
+                    .trim()
+                    .strip_prefix("0x")
+                    .ok_or_else(|| self.cast_error(data_type, "must start with 0x"))?;
+                Value::Bytes(parse_hex(hex).map_err(|msg| self.cast_error(data_type, &msg))?)
+            }
+            (Value::Integer(val), DataType::String) => Value::String(val.to_string()),
+            (Value::Number(val), DataType::String) => Value::String(val.to_string()),
+            (Value::Boolean(val), DataType::String) => Value::String(val.to_string()),
+            (Value::Bytes(_), DataType::String) => Value::String(self.to_literal()),
+            _ => return Err(self.cast_error(data_type, "is not supported")),
+        };
+        Ok(value)
+    }
+
+    fn cast_error(&self, data_type: DataType, reason: &str) -> Error {
+        Error::invalid_type(&format!(
+            "failed to cast {:?} to {} - {}",
+            self, data_type, reason
+        ))
+    }
+
+    /// 宽松解析，无法识别的内容均作为字符串
+    ///
+    /// 包含 `'` 时去掉引号作为字符串；包含 `true`/`false` 时尝试解析为 Boolean；
+    /// 包含 `.` 时尝试解析为 Number；否则尝试解析为 Integer
+    pub fn parse_lenient(s: &str) -> Value {
+        if s.contains('\'') {
+            return Value::String(s.replace('\'', ""));
+        }
+
+        if s.contains("false") || s.contains("true") {
+            s.parse::<bool>()
+                .map(Value::Boolean)
+                .unwrap_or_else(|_| Value::String(s.to_owned()))
+        } else if s.contains('.') {
+            s.parse::<f64>()
+                .map(Value::Number)
+                .unwrap_or_else(|_| Value::String(s.to_owned()))
+        } else if matches!(s, "null" | "NULL" | "Null" | "nil" | "Nil") {
+            Value::Nil
         } else {
-            return false;
+            s.parse::<i64>()
+                .map(Value::Integer)
+                .unwrap_or_else(|_| Value::String(s.to_owned()))
+        }
+    }
+
+    /// 转为可被 `FromStr` 无损解析回来的字面量
+    pub fn to_literal(&self) -> String {
+        match self {
+            Value::String(val) => format!("{:?}", val),
+            Value::Integer(val) => val.to_string(),
+            Value::Number(val) if val.is_nan() => "nan".to_owned(),
+            Value::Number(val) if val.is_infinite() => {
+                if *val > 0.0 { "inf" } else { "-inf" }.to_owned()
+            }
+            Value::Number(val) => {
+                let literal = format!("{:?}", val);
+                if literal.contains(['.', 'e', 'E']) {
+                    literal
+                } else {
+                    format!("{}.0", literal)
+                }
+            }
+            Value::Boolean(val) => val.to_string(),
+            Value::Bytes(val) => {
+                let hex: String = val.0.iter().map(|b| format!("{:02x}", b)).collect();
+                format!("0x{}", hex)
+            }
+            Value::Array(val) => {
+                let items: Vec<String> = val.iter().map(|val| val.to_literal()).collect();
+                format!("[{}]", items.join(", "))
+            }
+            Value::Nil => "nil".to_owned(),
         }
     }
 }
 
 #[test]
-fn from_value() {
-    let val = "10".parse::<Value>().unwrap();
+fn from_str() {
+    assert_eq!(Value::Integer(10), "10".parse::<Value>().unwrap());
+    assert_eq!(Value::Integer(-10), " -10 ".parse::<Value>().unwrap());
+    assert_eq!(Value::Number(10.01), "10.01".parse::<Value>().unwrap());
+    assert_eq!(Value::Number(1.5e-3), "1.5e-3".parse::<Value>().unwrap());
+    assert_eq!(Value::Number(2e10), "2E10".parse::<Value>().unwrap());
+    assert_eq!(
+        Value::Number(f64::NEG_INFINITY),
+        "-inf".parse::<Value>().unwrap()
+    );
+    assert_eq!(Value::Boolean(true), "true".parse::<Value>().unwrap());
+    assert_eq!(Value::Nil, "nil".parse::<Value>().unwrap());
+    assert_eq!(Value::Nil, "NULL".parse::<Value>().unwrap());
+    assert_eq!(
+        Value::String("untrue.txt".to_owned()),
+        "\"untrue.txt\"".parse::<Value>().unwrap()
+    );
+    assert_eq!(
+        Value::String("it's \"10\"\n\u{e9}".to_owned()),
+        r#""it's \"10\"\n\u{e9}""#.parse::<Value>().unwrap()
+    );
+    assert_eq!(
+        Value::String("a\"b".to_owned()),
+        "'a\"b'".parse::<Value>().unwrap()
+    );
+    assert_eq!(
+        Value::Bytes(Bytes(vec![0x09, 0x12, 0xab])),
+        "0x0912AB".parse::<Value>().unwrap()
+    );
+    assert_eq!(Value::Bytes(Bytes(vec![])), "0x".parse::<Value>().unwrap());
+    assert_eq!(
+        Value::Array(vec![
+            Value::Integer(1),
+            Value::String("2".to_owned()),
+            Value::Array(vec![Value::Nil]),
+        ]),
+        "[1, \"2\", [nil],]".parse::<Value>().unwrap()
+    );
+    assert_eq!(Value::Array(vec![]), "[ ]".parse::<Value>().unwrap());
+
+    for s in [
+        "",
+        "untrue.txt",
+        "Name",
+        "10._",
+        "1.2.3",
+        "0x123",
+        "0xzz",
+        "0x0\u{e9}0",
+        "\"abc",
+        "'abc\\q'",
+        "[1, 2",
+        "[1 2]",
+        "10 11",
+        "99999999999999999999",
+        "e10",
+        ".",
+    ] {
+        assert!(s.parse::<Value>().is_err(), "{:?} should not parse", s);
+    }
+}
+
+#[test]
+fn to_literal() {
+    let values = vec![
+        Value::String("it's \"10\"\n".to_owned()),
+        Value::Integer(-10),
+        Value::Number(10.0),
+        Value::Number(1.5e-30),
+        Value::Number(f64::INFINITY),
+        Value::Boolean(false),
+        Value::Bytes(Bytes(vec![0x09, 0x12])),
+        Value::Array(vec![Value::Integer(1), Value::Array(vec![Value::Nil])]),
+        Value::Nil,
+    ];
+    for value in values {
+        assert_eq!(value, value.to_literal().parse::<Value>().unwrap());
+    }
+    assert_eq!("10.0", Value::Number(10.0).to_literal());
+    assert_eq!("0x0912", Value::Bytes(Bytes(vec![0x09, 0x12])).to_literal());
+    assert!(Value::Number(f64::NAN)
+        .to_literal()
+        .parse::<Value>()
+        .map(|val| matches!(val, Value::Number(val) if val.is_nan()))
+        .unwrap());
+}
+
+#[test]
+#[allow(clippy::zero_prefixed_literal)]
+fn parse_lenient() {
+    let val = Value::parse_lenient("10");
     assert_eq!(Value::Integer(10), val);
 
-    let val = "false".parse::<Value>().unwrap();
+    let val = Value::parse_lenient("false");
     assert_eq!(Value::Boolean(false), val);
 
-    let val = "true".parse::<Value>().unwrap();
+    let val = Value::parse_lenient("true");
     assert_eq!(Value::Boolean(true), val);
 
-    let val = "10.01".parse::<Value>().unwrap();
+    let val = Value::parse_lenient("10.01");
     assert_eq!(Value::Number(10.01), val);
 
-    let val = "10._".parse::<Value>().unwrap();
+    let val = Value::parse_lenient("10._");
     assert_eq!(Value::String("10._".to_string()), val);
 
-    let val = "'10'".parse::<Value>().unwrap();
+    let val = Value::parse_lenient("'10'");
     assert_eq!(Value::String("10".to_string()), val);
 
-    let val = "Name".parse::<Value>().unwrap();
+    let val = Value::parse_lenient("Name");
     assert_eq!(Value::String("Name".to_string()), val);
 
-    let val = "Nil".parse::<Value>().unwrap();
+    let val = Value::parse_lenient("Nil");
     assert_eq!(Value::Nil, val);
 
-    let val = "nil".parse::<Value>().unwrap();
+    let val = Value::parse_lenient("nil");
     assert_eq!(Value::Nil, val);
 
-    let val = "Null".parse::<Value>().unwrap();
+    let val = Value::parse_lenient("Null");
     assert_eq!(Value::Nil, val);
 
-    let val = "NULL".parse::<Value>().unwrap();
+    let val = Value::parse_lenient("NULL");
     assert_eq!(Value::Nil, val);
 
-    let val = "null".parse::<Value>().unwrap();
+    let val = Value::parse_lenient("null");
     assert_eq!(Value::Nil, val);
 
     let array = vec![01_i32, 02_i32, 03_i32];
@@ -297,6 +740,7 @@ fn into_value() {
 }
 
 #[test]
+#[allow(clippy::unnecessary_fallible_conversions)]
 fn try_from_value() {
     assert_eq!(Value::Number(10.0), Value::try_from(10.0_f64).unwrap());
     assert_eq!(Value::Boolean(false), Value::try_from(false).unwrap());
@@ -308,6 +752,99 @@ fn try_from_value() {
     assert_eq!(Value::Nil, Value::try_from(()).unwrap());
 }
 
+#[test]
+fn try_from_integer_checked() {
+    assert_eq!(255_u8, u8::try_from(Value::Integer(255)).unwrap());
+    assert!(u8::try_from(Value::Integer(256)).is_err());
+    assert!(u32::try_from(Value::Integer(-1)).is_err());
+    assert!(i8::try_from(Value::Integer(-129)).is_err());
+    assert_eq!(i64::MAX, i64::try_from(Value::Integer(i64::MAX)).unwrap());
+}
+
+#[test]
+fn cast() {
+    let ok = |val: Value, t: DataType| val.cast(t).unwrap();
+    let err = |val: Value, t: DataType| {
+        let err = val.cast(t).unwrap_err();
+        assert_eq!(Error::invalid_type("").get_code(), err.get_code());
+    };
+
+    assert_eq!(
+        Value::Integer(10),
+        ok(Value::Integer(10), DataType::Integer)
+    );
+    assert_eq!(
+        Value::Number(10.0),
+        ok(Value::Integer(10), DataType::Number)
+    );
+    assert_eq!(
+        Value::Integer(10),
+        ok(Value::Number(10.0), DataType::Integer)
+    );
+    err(Value::Number(10.5), DataType::Integer);
+    err(Value::Number(f64::NAN), DataType::Integer);
+    err(Value::Number(1e19), DataType::Integer);
+    err(Value::Integer(i64::MAX), DataType::Number);
+    err(Value::Integer((1 << 53) + 1), DataType::Number);
+
+    assert_eq!(
+        Value::Integer(1),
+        ok(Value::Boolean(true), DataType::Integer)
+    );
+    assert_eq!(
+        Value::Boolean(false),
+        ok(Value::Integer(0), DataType::Boolean)
+    );
+    err(Value::Integer(2), DataType::Boolean);
+    err(Value::Number(1.0), DataType::Boolean);
+
+    assert_eq!(
+        Value::Integer(-10),
+        ok(Value::from(" -10"), DataType::Integer)
+    );
+    assert_eq!(
+        Value::Number(1.5e3),
+        ok(Value::from("1.5e3"), DataType::Number)
+    );
+    assert_eq!(
+        Value::Boolean(true),
+        ok(Value::from("true"), DataType::Boolean)
+    );
+    err(Value::from("10.5"), DataType::Integer);
+    err(Value::from("inf"), DataType::Number);
+    err(Value::from("yes"), DataType::Boolean);
+    assert_eq!(
+        "10",
+        format!("{}", ok(Value::Integer(10), DataType::String))
+    );
+    assert_eq!(
+        "10.5",
+        format!("{}", ok(Value::Number(10.5), DataType::String))
+    );
+    assert_eq!(
+        "false",
+        format!("{}", ok(Value::Boolean(false), DataType::String))
+    );
+
+    assert_eq!(
+        Value::Bytes(Bytes(vec![0x09, 0x12])),
+        ok(Value::from("0x0912"), DataType::Bytes)
+    );
+    assert_eq!(
+        Value::from("0x0912"),
+        ok(Value::Bytes(Bytes(vec![0x09, 0x12])), DataType::String)
+    );
+    err(Value::from("0912"), DataType::Bytes);
+    err(Value::from("0x091"), DataType::Bytes);
+    err(Value::from("0x0\u{e9}0"), DataType::Bytes);
+
+    assert_eq!(Value::Nil, ok(Value::Nil, DataType::Nil));
+    err(Value::Nil, DataType::String);
+    err(Value::Integer(10), DataType::Nil);
+    err(Value::Array(vec![]), DataType::String);
+    err(Value::Integer(10), DataType::Array);
+}
+
 #[test]
 #[should_panic(expected = "failed to parse")]
 fn try_from_value_failed() {
@@ -344,3 +881,27 @@ fn to_string() {
     );
     assert_eq!("Nil", format!("{}", Value::Nil));
 }
+
+#[cfg(feature = "serde")]
+#[test]
+fn serde_round_trip() {
+    let values = vec![
+        Value::String("He".to_owned()),
+        Value::Integer(-10),
+        Value::Number(10.02),
+        Value::Boolean(true),
+        Value::Bytes(Bytes(vec![0x09_u8, 0x12])),
+        Value::Array(vec![Value::Integer(1), Value::String("1".to_owned())]),
+        Value::Nil,
+    ];
+    for value in values {
+        let json = serde_json::to_string(&value).unwrap();
+        assert_eq!(value, serde_json::from_str::<Value>(&json).unwrap());
+    }
+
+    assert_eq!(
+        r#"{"Integer":10}"#,
+        serde_json::to_string(&Value::Integer(10)).unwrap()
+    );
+    assert_eq!(r#""Nil""#, serde_json::to_string(&Value::Nil).unwrap());
+}